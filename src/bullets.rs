@@ -1,95 +1,74 @@
-use std::{fmt::Display, hash::Hash};
-
 use bevy::{
     asset::{AssetServer, Assets},
     color::Color,
     math::{Vec2, Vec3},
-    prelude::{default, Bundle, Circle, Commands, Component, Mesh, Res, ResMut, Transform},
-    sprite::{ColorMaterial, MaterialMesh2dBundle, Mesh2dHandle, SpriteBundle},
-    utils::HashMap,
+    prelude::{
+        default, Added, Bundle, Circle, Commands, Component, DespawnRecursiveExt, Entity,
+        EventReader, EventWriter, Mesh, Query, Res, ResMut, Transform, Without,
+    },
+    sprite::{ColorMaterial, MaterialMesh2dBundle, Mesh2dHandle, Sprite, SpriteBundle},
+};
+use serde::Deserialize;
+
+use crate::{
+    tank::Tank,
+    terrain::Terrain,
+    utils::{ImpactTarget, ProjectileDespawned, ProjectileImpact, ProjectileSpawned},
+    weapons::{SplitTrigger, WeaponAppearance, WeaponBehavior, WeaponDefinition, WeaponId},
 };
-use enum_iterator::Sequence;
 
-use crate::utils::BulletFn;
+/// Number of fixed steps per second projectiles are simulated with.
+pub const BALLISTICS_RATE: f64 = 60.0;
+/// Launch speed in world units per second per point of fire velocity.
+pub const LAUNCH_SPEED: f32 = 100.0;
+/// Fraction of its velocity a projectile with a drag to mass ratio of 1 loses per second.
+const AIR_RESISTANCE: f32 = 0.05;
+/// Height above the tank center projectiles are spawned at.
+pub const MUZZLE_OFFSET: f32 = 20.0;
+/// Width the terrain slope under rolling projectiles is averaged over.
+const ROLL_SLOPE_WIDTH: f32 = 8.0;
+/// Height above the surface rolling projectiles are kept at.
+pub const ROLL_CLEARANCE: f32 = 4.0;
+/// Largest height difference rolling projectiles follow the surface across in one step. Higher
+/// rises stop them like a wall, deeper drops let them fall.
+const ROLL_STEP_HEIGHT: f32 = 10.0;
 
 #[derive(Component)]
 pub struct BulletCollider {}
 
-#[derive(Component, Eq, PartialEq, Clone, Copy, Sequence)]
-pub enum BulletType {
-    RegularBullet,
-    FireBullet,
-    Nuke,
-}
+/// Projectile moved by the physics engine instead of `move_bullets`, see the `physics` feature.
+#[cfg_attr(not(feature = "physics"), allow(dead_code))]
+#[derive(Component)]
+pub struct PhysicsBody {}
 
-impl Display for BulletType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let type_str = match self {
-            BulletType::RegularBullet => "RegularBullet",
-            BulletType::FireBullet => "FireBullet",
-            BulletType::Nuke => "Nuke",
-        };
-        f.write_str(type_str)
-    }
-}
+/// Keeps the turn from ending while the entity exists, for effects outliving their projectile.
+#[derive(Component)]
+pub struct TurnHold {}
 
-impl Hash for BulletType {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        let value = self.get_int_value();
-        state.write(&value.to_le_bytes())
-    }
+/// How explosion damage decreases towards the edge of the blast radius.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+pub enum Falloff {
+    /// Full damage within the whole radius.
+    Constant,
+    #[default]
+    Linear,
+    /// Damage drops off quickly away from the center.
+    Quadratic,
 }
 
-impl BulletType {
-    pub fn get_int_value(&self) -> u32 {
-        match self {
-            BulletType::RegularBullet => 0,
-            BulletType::FireBullet => 1,
-            BulletType::Nuke => 2,
+impl Falloff {
+    /// Returns the fraction of the damage dealt at `distance` from the center.
+    pub fn factor(&self, distance: f32, radius: f32) -> f32 {
+        if distance > radius {
+            return 0.0;
         }
-    }
-
-    pub fn get_from_int(value: u32) -> BulletType {
-        match value {
-            0 => BulletType::RegularBullet,
-            1 => BulletType::FireBullet,
-            2 => BulletType::Nuke,
-            _ => panic!("Bullet with this ID doesn't exist"),
-        }
-    }
-
-    pub fn get_bullet_from_type(&self) -> BulletFn {
-        match self {
-            BulletType::RegularBullet => NORMAL_BULLET,
-            BulletType::FireBullet => FIRE_BULLET,
-            BulletType::Nuke => NUKE,
-        }
-    }
-
-    pub fn get_cost(&self) -> u32 {
-        match self {
-            BulletType::RegularBullet => 0,
-            BulletType::FireBullet => 10,
-            BulletType::Nuke => 100,
-        }
-    }
-
-    pub fn get_max_count(&self) -> u32 {
+        let remaining = 1.0 - distance / radius.max(f32::EPSILON);
         match self {
-            BulletType::RegularBullet => u32::MAX,
-            BulletType::FireBullet => 20,
-            BulletType::Nuke => 3,
+            Falloff::Constant => 1.0,
+            Falloff::Linear => remaining,
+            Falloff::Quadratic => remaining * remaining,
         }
     }
-
-    pub fn init_bullets() -> HashMap<BulletType, BulletCount> {
-        let mut map = HashMap::new();
-        map.insert(BulletType::RegularBullet, BulletCount::Unlimited);
-        // TODO remove
-        map.insert(BulletType::FireBullet, BulletCount::Count(10));
-        map.insert(BulletType::Nuke, BulletCount::Count(10));
-        map
-    }
 }
 
 #[derive(Component, Clone, Copy)]
@@ -116,13 +95,167 @@ impl BulletCount {
 
 #[derive(Component)]
 pub struct Bullet {
+    /// Velocity in world units per second.
     pub velocity_shot: Vec2,
-    pub velocity_gravity: Vec2,
+    /// Position before the last fixed step, collisions are tested along the travelled segment.
+    pub previous_position: Vec2,
     pub damage: u32,
-    pub radius: u32,
+    /// Radius of the explosion on impact.
+    pub radius: f32,
+    pub falloff: Falloff,
+    /// Drag to mass ratio of the weapon, see `WeaponDefinition::exposure`.
+    pub exposure: f32,
+    pub behaviors: Vec<WeaponBehavior>,
+    /// Seconds since the projectile was fired.
+    pub flight_time: f32,
+    /// Whether the projectile has moved upwards, `SplitTrigger::Apex` only fires after that.
+    pub risen: bool,
+    /// Rolling along the ground instead of flying, see `WeaponBehavior::Roll`.
+    pub rolling: bool,
+    /// Burrowing through the ground, see `WeaponBehavior::Dig`.
+    pub digging: bool,
+    /// World units travelled through the ground so far.
+    pub dug: f32,
     pub owner: u32,
 }
 
+impl Bullet {
+    /// Whether a `Fuse` behavior makes the projectile explode in mid-air now.
+    pub fn fuse_expired(&self) -> bool {
+        self.behaviors.iter().any(|behavior| match behavior {
+            WeaponBehavior::Fuse { seconds } => self.flight_time >= *seconds,
+            _ => false,
+        })
+    }
+
+    /// Returns the friction of a `Roll` behavior.
+    pub fn roll_friction(&self) -> Option<f32> {
+        self.behaviors.iter().find_map(|behavior| match behavior {
+            WeaponBehavior::Roll { friction } => Some(*friction),
+            _ => None,
+        })
+    }
+
+    /// Whether a rolling projectile has come to a stop, see `rolling_step`.
+    pub fn at_rest(&self) -> bool {
+        self.rolling && self.velocity_shot == Vec2::ZERO
+    }
+
+    /// Returns the tunnel length, tunnel radius and whether to explode at the end of a `Dig`
+    /// behavior.
+    pub fn dig(&self) -> Option<(f32, f32, bool)> {
+        self.behaviors.iter().find_map(|behavior| match behavior {
+            WeaponBehavior::Dig {
+                distance,
+                radius,
+                detonate,
+            } => Some((*distance, *radius, *detonate)),
+            _ => None,
+        })
+    }
+
+    /// Whether the projectile can still burrow further instead of exploding on the ground.
+    pub fn can_dig(&self) -> bool {
+        matches!(self.dig(), Some((distance, _, _)) if self.dug < distance)
+    }
+
+    /// Whether a digging projectile reached the end of its tunnel.
+    pub fn tunnel_finished(&self) -> bool {
+        self.digging && !self.can_dig()
+    }
+
+    /// Returns the count, spread and child weapon of a `Cluster` behavior that should split now.
+    pub fn cluster_split(&self) -> Option<(u32, f32, &WeaponDefinition)> {
+        self.behaviors.iter().find_map(|behavior| match behavior {
+            WeaponBehavior::Cluster {
+                count,
+                spread,
+                trigger,
+                child,
+            } => {
+                let triggered = match trigger {
+                    SplitTrigger::Apex => self.risen && self.velocity_shot.y <= 0.0,
+                    SplitTrigger::Timer(seconds) => self.flight_time >= *seconds,
+                };
+                triggered.then_some((*count, *spread, child.as_ref()))
+            }
+            _ => None,
+        })
+    }
+}
+
+/// Returns the launch velocity in world units per second for a shot at `angle` with the fire
+/// velocity set by the player.
+pub fn launch_velocity(angle: f32, fire_velocity: f32) -> Vec2 {
+    let speed = fire_velocity * LAUNCH_SPEED;
+    Vec2::new(-angle.cos() * speed, angle.sin() * speed)
+}
+
+/// Advances a projectile by `delta` seconds under gravity, `wind` and air drag, returning the
+/// new position and velocity. Shared by `move_bullets` and the trajectory preview.
+pub fn ballistic_step(
+    position: Vec2,
+    velocity: Vec2,
+    exposure: f32,
+    wind: f32,
+    gravity: f32,
+    delta: f32,
+) -> (Vec2, Vec2) {
+    let air = Vec2::new(wind, 0.0) - AIR_RESISTANCE * velocity;
+    let acceleration = Vec2::new(0.0, -gravity) + exposure * air;
+
+    // s0 + v0 * t + 0.5 * a * t * t
+    let position = position + velocity * delta + 0.5 * acceleration * delta * delta;
+    (position, velocity + acceleration * delta)
+}
+
+/// Advances a projectile rolling along the terrain surface by `delta` seconds, accelerated
+/// downhill by `gravity` and slowed by `friction`, returning the new position and velocity.
+/// The velocity is exactly zero once friction or a wall holds the projectile in place. Returns
+/// `None` if the surface drops away, the projectile flies on with `ballistic_step` then.
+pub fn rolling_step(
+    terrain: &Terrain,
+    position: Vec2,
+    velocity: Vec2,
+    friction: f32,
+    gravity: f32,
+    delta: f32,
+) -> Option<(Vec2, Vec2)> {
+    // look past the averaged slope for walls and edges in the direction of travel
+    let ahead = position.x + velocity.x * delta + velocity.x.signum() * ROLL_SLOPE_WIDTH / 2.0;
+    let rise = terrain.height_at(ahead) + ROLL_CLEARANCE - position.y;
+    if rise > ROLL_STEP_HEIGHT {
+        return Some((position, Vec2::ZERO));
+    }
+    if rise < -ROLL_STEP_HEIGHT {
+        return None;
+    }
+    let tangent = terrain.tangent_at(position.x, ROLL_SLOPE_WIDTH);
+    let downhill = -gravity * tangent.y;
+    let speed = velocity.dot(tangent) + downhill * delta;
+    // friction slows the projectile down but never turns it around
+    let slowed = (speed.abs() - friction * delta).max(0.0);
+    let speed = if slowed > 0.0 {
+        speed.signum() * slowed
+    } else if downhill.abs() > friction {
+        // turning around on a slope too steep to stay on
+        downhill.signum() * (downhill.abs() - friction) * delta
+    } else {
+        return Some((position, Vec2::ZERO));
+    };
+    let x = position.x + speed * tangent.x * delta;
+    let position = Vec2::new(x, terrain.height_at(x) + ROLL_CLEARANCE);
+    Some((position, terrain.tangent_at(x, ROLL_SLOPE_WIDTH) * speed))
+}
+
+/// Puts a projectile that touched the ground at `contact` onto the surface, keeping the part of
+/// its velocity along the slope.
+pub fn start_rolling(terrain: &Terrain, contact: Vec2, velocity: Vec2) -> (Vec2, Vec2) {
+    let tangent = terrain.tangent_at(contact.x, ROLL_SLOPE_WIDTH);
+    let position = Vec2::new(contact.x, terrain.height_at(contact.x) + ROLL_CLEARANCE);
+    (position, tangent * velocity.dot(tangent))
+}
+
 #[derive(Bundle)]
 pub struct BulletMeshBundle {
     pub bullet: Bullet,
@@ -151,120 +284,281 @@ impl<'a> BulletInfo<'a> {
     }
 }
 
-pub const NORMAL_BULLET: BulletFn = |commands: &mut Commands,
-                                     meshes: &mut ResMut<Assets<Mesh>>,
-                                     materials: &mut ResMut<Assets<ColorMaterial>>,
-                                     _: &Res<AssetServer>,
-                                     info: &BulletInfo| {
-    let offset_origin = Vec3 {
+/// Spawns a projectile of `weapon` at `info.origin` as described by `info`.
+pub fn spawn_bullet(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    asset_server: &Res<AssetServer>,
+    weapon: &WeaponDefinition,
+    info: &BulletInfo,
+) {
+    let origin = Vec3 {
         x: info.origin.x,
-        y: info.origin.y + 20.0,
+        y: info.origin.y,
         z: 0.0,
     };
-    commands.spawn((
-        BulletMeshBundle {
-            bullet: Bullet {
-                velocity_shot: *info.velocity,
-                velocity_gravity: Vec2 { x: 0.0, y: 9.81 },
-                // TODO implement
-                damage: 10,
-                radius: 10,
-                owner: info.owner,
-            },
-            mesh_bundle: MaterialMesh2dBundle {
-                mesh: Mesh2dHandle(meshes.add(Circle { radius: 1.0 })),
-                material: materials.add(Color::BLACK),
-                transform: Transform {
-                    translation: offset_origin,
-                    scale: Vec3 {
-                        x: 10.0,
-                        y: 10.0,
-                        z: 1.0,
+    let bullet = Bullet {
+        velocity_shot: *info.velocity,
+        previous_position: origin.truncate(),
+        damage: weapon.damage,
+        radius: weapon.radius,
+        falloff: weapon.falloff,
+        exposure: weapon.exposure(),
+        behaviors: weapon.behaviors.clone(),
+        flight_time: 0.0,
+        risen: false,
+        rolling: false,
+        digging: false,
+        dug: 0.0,
+        owner: info.owner,
+    };
+    match &weapon.appearance {
+        WeaponAppearance::Mesh { radius, color } => {
+            commands.spawn((
+                BulletMeshBundle {
+                    bullet,
+                    mesh_bundle: MaterialMesh2dBundle {
+                        mesh: Mesh2dHandle(meshes.add(Circle { radius: *radius })),
+                        material: materials.add(Color::srgb(color.0, color.1, color.2)),
+                        transform: Transform {
+                            translation: origin,
+                            ..default()
+                        },
+                        ..default()
                     },
-                    ..default()
                 },
-                ..default()
-            },
-        },
-        BulletType::RegularBullet,
-    ));
-};
-
-pub const FIRE_BULLET: BulletFn = |commands: &mut Commands,
-                                   meshes: &mut ResMut<Assets<Mesh>>,
-                                   materials: &mut ResMut<Assets<ColorMaterial>>,
-                                   // TODO investigate adding an eventwriter for desapwn event ->  this could instead
-                                   // be used to handle the state for triggering a new players turn
-                                   // ->  each player only fires once, but this bullet can spawn
-                                   // more bullets -> cluster.
-                                   // This despawn method can handle these effects by allowing per
-                                   // bullet overrides. The cluster can delegate the spawning of
-                                   // the event for later.
-                                   _: &Res<AssetServer>,
-                                   info: &BulletInfo| {
-    let offset_origin = Vec3 {
-        x: info.origin.x,
-        y: info.origin.y + 20.0,
-        z: 0.0,
-    };
-    commands.spawn((
-        BulletMeshBundle {
-            bullet: Bullet {
-                velocity_shot: *info.velocity,
-                velocity_gravity: Vec2 { x: 0.0, y: 9.81 },
-                // TODO implement
-                damage: 10,
-                radius: 10,
-                owner: info.owner,
-            },
-            mesh_bundle: MaterialMesh2dBundle {
-                mesh: Mesh2dHandle(meshes.add(Circle { radius: 2.0 })),
-                material: materials.add(Color::srgb(1.0, 0.0, 0.0)),
-                transform: Transform {
-                    translation: offset_origin,
-                    scale: Vec3 {
-                        x: 10.0,
-                        y: 10.0,
-                        z: 1.0,
+                weapon.id(),
+            ));
+        }
+        WeaponAppearance::Sprite { path, size } => {
+            commands.spawn((
+                BulletSpriteBundle {
+                    bullet,
+                    sprite_bundle: SpriteBundle {
+                        sprite: Sprite {
+                            custom_size: size.map(Vec2::splat),
+                            ..default()
+                        },
+                        texture: asset_server.load(path.clone()),
+                        transform: Transform {
+                            translation: origin,
+                            ..default()
+                        },
+                        ..default()
                     },
-                    ..default()
                 },
-                ..default()
-            },
-        },
-        BulletType::FireBullet,
-    ));
-};
+                weapon.id(),
+            ));
+        }
+    }
+}
 
-pub const NUKE: BulletFn = |commands: &mut Commands,
-                            _: &mut ResMut<Assets<Mesh>>,
-                            _: &mut ResMut<Assets<ColorMaterial>>,
-                            asset_server: &Res<AssetServer>,
-                            info: &BulletInfo| {
-    let offset_origin = Vec3 {
-        x: info.origin.x,
-        y: info.origin.y + 20.0,
-        z: 0.0,
+/// Impact handler fanning out the sub-munitions of cluster projectiles whose trigger fired,
+/// `detonate_projectiles` removes the parent afterwards.
+pub fn split_clusters(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    asset_server: Res<AssetServer>,
+    bullets: Query<(&Bullet, &Transform)>,
+    mut reader: EventReader<ProjectileImpact>,
+) {
+    for impact in reader.read() {
+        if impact.target != ImpactTarget::Air {
+            continue;
+        }
+        let (bullet, transform) = if let Ok(bullet) = bullets.get(impact.entity) {
+            bullet
+        } else {
+            continue;
+        };
+        let (count, spread, child) = if let Some(split) = bullet.cluster_split() {
+            split
+        } else {
+            continue;
+        };
+        for index in 0..count {
+            let offset = if count > 1 {
+                spread * (index as f32 / (count - 1) as f32 - 0.5)
+            } else {
+                0.0
+            };
+            let velocity = Vec2::from_angle(offset).rotate(bullet.velocity_shot);
+            let info = BulletInfo::new(&velocity, &transform.translation, bullet.owner);
+            spawn_bullet(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &asset_server,
+                child,
+                &info,
+            );
+        }
+    }
+}
+
+/// Sends a `ProjectileSpawned` event for every projectile added since the last run.
+pub fn announce_projectiles(
+    bullets: Query<(Entity, &Bullet, &WeaponId), Added<Bullet>>,
+    mut writer: EventWriter<ProjectileSpawned>,
+) {
+    for (entity, bullet, weapon) in &bullets {
+        writer.send(ProjectileSpawned {
+            entity,
+            weapon: weapon.clone(),
+            owner: bullet.owner,
+        });
+    }
+}
+
+/// Carves the tunnel along the path of this step of digging projectiles, from where they hit
+/// the ground, and removes diggers without a detonation at the end of their tunnel. Only steps
+/// ending underground start or continue digging, diggers passing through thin ground or breaking
+/// out of it fly on until they hit it again. Diggers reaching bedrock or the bottom of the map
+/// get stuck and explode there.
+pub fn dig_tunnels(
+    mut commands: Commands,
+    mut terrain: ResMut<Terrain>,
+    mut bullets: Query<(Entity, &mut Bullet, &WeaponId, &mut Transform)>,
+    mut tanks: Query<(&mut Tank, &Transform), Without<Bullet>>,
+    mut despawn_writer: EventWriter<ProjectileDespawned>,
+) {
+    for (entity, mut bullet, weapon, mut transform) in &mut bullets {
+        if !bullet.can_dig() {
+            continue;
+        }
+        let (distance, radius, detonate) = if let Some(dig) = bullet.dig() {
+            dig
+        } else {
+            continue;
+        };
+        let position = transform.translation.truncate();
+        let contact = terrain.raycast(bullet.previous_position, position);
+        let start = match contact {
+            Some(contact) => contact,
+            // the step lies inside the tunnel dug so far
+            None if bullet.digging => bullet.previous_position,
+            None => continue,
+        };
+        // bedrock and the bottom of the map can't be dug through, the digger gets stuck there
+        let stuck = terrain.first_unbreakable(start, position, radius / 2.0);
+        let position = stuck.unwrap_or(position);
+        // inside the own tunnel is still underground, only breaking out of the surface ends it
+        let underground = if bullet.digging {
+            position.y < terrain.height_at(position.x)
+        } else {
+            terrain.is_solid(position)
+        };
+        terrain.carve_segment(start, position, radius);
+        let length = start.distance(position);
+        for (mut tank, tank_transform) in &mut tanks {
+            tank.undermine(
+                tank_transform,
+                start.lerp(position, 0.5),
+                radius + length / 2.0,
+                bullet.owner,
+            );
+        }
+        bullet.digging = underground;
+        bullet.dug += length;
+        if stuck.is_some() {
+            // ends the tunnel, `bullet_collision` detonates the digger where it got stuck
+            bullet.digging = true;
+            bullet.dug = distance;
+            transform.translation = position.extend(transform.translation.z);
+            continue;
+        }
+        if bullet.can_dig() || detonate {
+            continue;
+        }
+        despawn_writer.send(ProjectileDespawned {
+            entity,
+            weapon: weapon.clone(),
+            owner: bullet.owner,
+            position,
+        });
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        app::{App, FixedUpdate},
+        prelude::{Fixed, MinimalPlugins, Time},
+        time::TimeUpdateStrategy,
     };
-    commands.spawn((
-        BulletSpriteBundle {
-            bullet: Bullet {
-                velocity_shot: *info.velocity,
-                velocity_gravity: Vec2 { x: 0.0, y: 9.81 },
-                // TODO implement
-                damage: 10,
-                radius: 10,
-                owner: info.owner,
-            },
-            sprite_bundle: SpriteBundle {
-                texture: asset_server.load("../assets/nuke.gif"),
-                transform: Transform {
-                    translation: offset_origin,
-                    ..default()
+
+    use super::*;
+    use crate::{move_bullets, terrain::map::MapRules, utils::GameState};
+
+    /// Fires a shot into an empty map and moves it with `move_bullets` over frames of the given
+    /// lengths, returning where the bullet is after `frames` have been repeated `repeat` times.
+    fn position_after(frames: &[Duration], repeat: usize) -> Vec2 {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(Time::<Fixed>::from_hz(BALLISTICS_RATE))
+            .insert_resource(GameState {
+                wind: 10.0,
+                ..GameState::default()
+            })
+            .insert_resource(MapRules::default())
+            .insert_resource(Terrain::empty(-1000, 1000, -100_000.0))
+            .add_systems(FixedUpdate, move_bullets);
+        let origin = Vec2::new(0.0, MUZZLE_OFFSET);
+        let bullet = app
+            .world_mut()
+            .spawn((
+                Bullet {
+                    velocity_shot: launch_velocity(1.0, 8.0),
+                    previous_position: origin,
+                    damage: 0,
+                    radius: 0.0,
+                    falloff: Falloff::Linear,
+                    exposure: 1.0,
+                    behaviors: Vec::new(),
+                    flight_time: 0.0,
+                    risen: false,
+                    rolling: false,
+                    digging: false,
+                    dug: 0.0,
+                    owner: 0,
                 },
-                ..default()
-            },
-        },
-        BulletType::Nuke,
-    ));
-};
+                Transform::from_translation(origin.extend(0.0)),
+            ))
+            .id();
+        // The first update only starts the clock.
+        app.update();
+        for _ in 0..repeat {
+            for frame in frames {
+                app.insert_resource(TimeUpdateStrategy::ManualDuration(*frame));
+                app.update();
+            }
+        }
+        app.world()
+            .get::<Transform>(bullet)
+            .unwrap()
+            .translation
+            .truncate()
+    }
+
+    #[test]
+    fn bullet_position_is_independent_of_frame_rate() {
+        let expected = position_after(&[Duration::from_micros(33_000)], 30);
+        assert_ne!(expected, Vec2::new(0.0, MUZZLE_OFFSET));
+        let split = position_after(
+            &[Duration::from_micros(16_500), Duration::from_micros(16_500)],
+            30,
+        );
+        assert_eq!(split, expected);
+    }
+
+    #[test]
+    fn repeated_shots_end_at_the_same_point() {
+        let frames = [Duration::from_millis(5), Duration::from_millis(27)];
+        assert_eq!(position_after(&frames, 20), position_after(&frames, 20));
+    }
+}
@@ -0,0 +1,408 @@
+use std::fmt::Display;
+
+use bevy::{
+    asset::{
+        io::Reader, Asset, AssetLoader, AssetServer, Assets, AsyncReadExt, Handle, LoadContext,
+        LoadState,
+    },
+    math::{UVec2, Vec2, Vec3},
+    prelude::{
+        default, Camera2d, Commands, Component, DespawnRecursiveExt, Entity, EventReader, Image,
+        Query, Res, ResMut, Resource, Transform, Visibility, With,
+    },
+    reflect::TypePath,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+    sprite::{Sprite, SpriteBundle, TextureAtlas, TextureAtlasLayout},
+    time::Time,
+};
+use rand::Rng;
+
+use crate::{
+    bullets::{Bullet, TurnHold},
+    tank::Tank,
+    terrain::map::MapRules,
+    utils::ProjectileImpact,
+    weapons::WeaponBehavior,
+    world::WorldBounds,
+};
+
+/// Frame time used for GIF frames without a delay, same as browsers.
+const DEFAULT_FRAME_SECONDS: f32 = 0.1;
+/// Height a knocked back tank is thrown to, as a fraction of the knockback distance.
+const KNOCKBACK_LIFT: f32 = 0.25;
+
+/// Frames of a GIF packed row by row into a texture atlas.
+#[derive(Asset, TypePath)]
+pub struct GifAnimation {
+    pub texture: Handle<Image>,
+    pub layout: Handle<TextureAtlasLayout>,
+    pub frame_size: UVec2,
+    /// Display time of every frame in seconds.
+    pub frame_seconds: Vec<f32>,
+}
+
+#[derive(Debug)]
+pub enum GifLoadError {
+    Io(std::io::Error),
+    Decode(gif::DecodingError),
+}
+
+impl Display for GifLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GifLoadError::Io(error) => write!(f, "Could not read gif: {}", error),
+            GifLoadError::Decode(error) => write!(f, "Could not decode gif: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for GifLoadError {}
+
+impl From<std::io::Error> for GifLoadError {
+    fn from(value: std::io::Error) -> Self {
+        GifLoadError::Io(value)
+    }
+}
+
+impl From<gif::DecodingError> for GifLoadError {
+    fn from(value: gif::DecodingError) -> Self {
+        GifLoadError::Decode(value)
+    }
+}
+
+/// Decodes every frame of a GIF. The first frame is also available as a still image under the
+/// `Frame0` label, e.g. `nuke.gif#Frame0`.
+#[derive(Default)]
+pub struct GifAnimationLoader;
+
+impl AssetLoader for GifAnimationLoader {
+    type Asset = GifAnimation;
+    type Settings = ();
+    type Error = GifLoadError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _: &'a (),
+        load_context: &'a mut LoadContext<'_>,
+    ) -> Result<GifAnimation, GifLoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(bytes.as_slice())?;
+        let width = decoder.width() as usize;
+        let height = decoder.height() as usize;
+
+        // frames only cover the changed part of the image, so they are drawn onto a canvas
+        let mut canvas = vec![0; width * height * 4];
+        let mut frames = Vec::new();
+        let mut frame_seconds = Vec::new();
+        while let Some(frame) = decoder.read_next_frame()? {
+            let previous = canvas.clone();
+            draw_frame(&mut canvas, width, height, frame, false);
+            frames.push(canvas.clone());
+            frame_seconds.push(if frame.delay == 0 {
+                DEFAULT_FRAME_SECONDS
+            } else {
+                frame.delay as f32 / 100.0
+            });
+            match frame.dispose {
+                gif::DisposalMethod::Background => {
+                    draw_frame(&mut canvas, width, height, frame, true)
+                }
+                gif::DisposalMethod::Previous => canvas = previous,
+                _ => {}
+            }
+        }
+        if frames.is_empty() {
+            frames.push(canvas);
+            frame_seconds.push(DEFAULT_FRAME_SECONDS);
+        }
+
+        let columns = (frames.len() as f32).sqrt().ceil() as usize;
+        let rows = frames.len().div_ceil(columns);
+        let atlas_width = columns * width;
+        let mut atlas = vec![0; atlas_width * rows * height * 4];
+        for (index, frame) in frames.iter().enumerate() {
+            let x = (index % columns) * width;
+            let y = (index / columns) * height;
+            for row in 0..height {
+                let start = ((y + row) * atlas_width + x) * 4;
+                atlas[start..start + width * 4]
+                    .copy_from_slice(&frame[row * width * 4..(row + 1) * width * 4]);
+            }
+        }
+
+        let frame_size = UVec2::new(width as u32, height as u32);
+        load_context.add_labeled_asset(
+            "Frame0".to_string(),
+            rgba_image(frame_size, frames.swap_remove(0)),
+        );
+        let texture = load_context.add_labeled_asset(
+            "Atlas".to_string(),
+            rgba_image(
+                UVec2::new(atlas_width as u32, (rows * height) as u32),
+                atlas,
+            ),
+        );
+        let layout = load_context.add_labeled_asset(
+            "Layout".to_string(),
+            TextureAtlasLayout::from_grid(frame_size, columns as u32, rows as u32, None, None),
+        );
+        Ok(GifAnimation {
+            texture,
+            layout,
+            frame_size,
+            frame_seconds,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["gif"]
+    }
+}
+
+/// Draws the opaque pixels of `frame` onto `canvas`, or clears its area when `clear` is set.
+fn draw_frame(canvas: &mut [u8], width: usize, height: usize, frame: &gif::Frame, clear: bool) {
+    for row in 0..frame.height as usize {
+        let y = frame.top as usize + row;
+        if y >= height {
+            break;
+        }
+        for column in 0..frame.width as usize {
+            let x = frame.left as usize + column;
+            if x >= width {
+                break;
+            }
+            let target = (y * width + x) * 4;
+            if clear {
+                canvas[target..target + 4].fill(0);
+                continue;
+            }
+            let source = (row * frame.width as usize + column) * 4;
+            if frame.buffer[source + 3] != 0 {
+                canvas[target..target + 4].copy_from_slice(&frame.buffer[source..source + 4]);
+            }
+        }
+    }
+}
+
+fn rgba_image(size: UVec2, data: Vec<u8>) -> Image {
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+/// Plays the frames of a `GifAnimation` once and removes itself afterwards.
+#[derive(Component)]
+pub struct ExplosionAnimation {
+    animation: Handle<GifAnimation>,
+    /// Width in world units, the height follows the aspect ratio of the GIF.
+    size: f32,
+    frame: usize,
+    elapsed: f32,
+}
+
+/// Camera shake fading out over time.
+#[derive(Resource, Default)]
+pub struct ScreenShake {
+    strength: f32,
+    duration: f32,
+    remaining: f32,
+}
+
+impl ScreenShake {
+    /// Starts shaking, unless a stronger shake is still running.
+    pub fn start(&mut self, strength: f32, seconds: f32) {
+        if self.current_strength() > strength {
+            return;
+        }
+        self.strength = strength;
+        self.duration = seconds;
+        self.remaining = seconds;
+    }
+
+    fn current_strength(&self) -> f32 {
+        if self.remaining <= 0.0 {
+            return 0.0;
+        }
+        self.strength * self.remaining / self.duration
+    }
+}
+
+/// Impact handler starting the explosion animation and screen shake of a projectile.
+pub fn start_impact_effects(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut shake: ResMut<ScreenShake>,
+    bullets: Query<&Bullet>,
+    mut reader: EventReader<ProjectileImpact>,
+) {
+    for impact in reader.read() {
+        let bullet = if let Ok(bullet) = bullets.get(impact.entity) {
+            bullet
+        } else {
+            continue;
+        };
+        for behavior in &bullet.behaviors {
+            match behavior {
+                WeaponBehavior::ExplosionAnimation { path, size } => {
+                    commands.spawn((
+                        SpriteBundle {
+                            // hidden until the frames are decoded
+                            visibility: Visibility::Hidden,
+                            transform: Transform::from_translation(impact.position.extend(3.0)),
+                            ..default()
+                        },
+                        ExplosionAnimation {
+                            animation: asset_server.load(path),
+                            size: *size,
+                            frame: 0,
+                            elapsed: 0.0,
+                        },
+                        TurnHold {},
+                    ));
+                }
+                WeaponBehavior::ScreenShake { strength, seconds } => {
+                    shake.start(*strength, *seconds);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Seconds a tank thrown upwards with `vertical_velocity` stays in the air before coming back
+/// down to the height it was thrown from.
+pub fn knockback_airtime(vertical_velocity: f32, gravity: f32) -> f32 {
+    2.0 * vertical_velocity / gravity
+}
+
+/// Impact handler throwing tanks away from projectiles with a `Knockback` behavior. The sideways
+/// speed is derived from the airtime, so thrown tanks land the knockback distance away on flat
+/// ground unless they hit a wall first, and take fall damage as usual when landing.
+pub fn knock_back_tanks(
+    bullets: Query<&Bullet>,
+    rules: Res<MapRules>,
+    mut tanks: Query<(&mut Tank, &Transform)>,
+    mut reader: EventReader<ProjectileImpact>,
+) {
+    for impact in reader.read() {
+        let bullet = if let Ok(bullet) = bullets.get(impact.entity) {
+            bullet
+        } else {
+            continue;
+        };
+        for behavior in &bullet.behaviors {
+            let distance = if let WeaponBehavior::Knockback { distance } = behavior {
+                *distance
+            } else {
+                continue;
+            };
+            for (mut tank, transform) in &mut tanks {
+                let offset = tank.distance_to(transform, impact.position);
+                if offset > bullet.radius {
+                    continue;
+                }
+                let push = distance * (1.0 - offset / bullet.radius);
+                if push <= 0.0 {
+                    continue;
+                }
+                let direction = if transform.translation.x < impact.position.x {
+                    -1.0
+                } else {
+                    1.0
+                };
+                let lift = (2.0 * rules.tank_gravity() * push * KNOCKBACK_LIFT).sqrt();
+                tank.horizontal_velocity =
+                    direction * push / knockback_airtime(lift, rules.tank_gravity());
+                tank.vertical_velocity = lift;
+                tank.undermined_by = Some(impact.owner);
+            }
+        }
+    }
+}
+
+pub fn play_explosion_animations(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    animations: Res<Assets<GifAnimation>>,
+    mut explosions: Query<(
+        Entity,
+        &mut ExplosionAnimation,
+        &mut Sprite,
+        Option<&mut TextureAtlas>,
+    )>,
+) {
+    for (entity, mut explosion, mut sprite, atlas) in &mut explosions {
+        let animation = if let Some(animation) = animations.get(&explosion.animation) {
+            animation
+        } else {
+            // don't hold up the turn forever for a missing file
+            if let Some(LoadState::Failed(_)) = asset_server.get_load_state(&explosion.animation) {
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        };
+        let mut atlas = if let Some(atlas) = atlas {
+            atlas
+        } else {
+            let aspect = animation.frame_size.y as f32 / animation.frame_size.x as f32;
+            sprite.custom_size = Some(Vec2::new(explosion.size, explosion.size * aspect));
+            commands.entity(entity).insert((
+                animation.texture.clone(),
+                TextureAtlas {
+                    layout: animation.layout.clone(),
+                    index: 0,
+                },
+                Visibility::Visible,
+            ));
+            continue;
+        };
+        explosion.elapsed += time.delta_seconds();
+        while explosion.elapsed >= animation.frame_seconds[explosion.frame] {
+            explosion.elapsed -= animation.frame_seconds[explosion.frame];
+            explosion.frame += 1;
+            if explosion.frame == animation.frame_seconds.len() {
+                commands.entity(entity).despawn_recursive();
+                break;
+            }
+        }
+        atlas.index = explosion.frame.min(animation.frame_seconds.len() - 1);
+    }
+}
+
+pub fn shake_camera(
+    time: Res<Time>,
+    bounds: Res<WorldBounds>,
+    mut shake: ResMut<ScreenShake>,
+    mut cameras: Query<&mut Transform, With<Camera2d>>,
+) {
+    if shake.remaining <= 0.0 {
+        return;
+    }
+    shake.remaining -= time.delta_seconds();
+    let strength = shake.current_strength();
+    let mut rng = rand::thread_rng();
+    for mut transform in &mut cameras {
+        let offset = Vec2::new(
+            rng.gen_range(-1.0..=1.0) * strength,
+            rng.gen_range(-1.0..=1.0) * strength,
+        );
+        let center = bounds.center() + offset;
+        transform.translation = Vec3::new(center.x, center.y, transform.translation.z);
+    }
+}
@@ -6,10 +6,10 @@ use bevy::{
 };
 
 use crate::{
-    bullets::BulletType,
     tank::Tank,
     ui::battle::BattleMessage,
     utils::{GameMode, GameState, Player},
+    weapons::WeaponRegistry,
     UiMessage,
 };
 
@@ -47,6 +47,7 @@ pub fn handle_keypress(
     query: Query<(&Player, &Tank)>,
     keys: Res<ButtonInput<KeyCode>>,
     state: Res<GameState>,
+    weapons: Res<WeaponRegistry>,
     mut writer: EventWriter<UiMessage>,
 ) {
     if state.firing || state.mode != GameMode::Battle {
@@ -90,8 +91,8 @@ pub fn handle_keypress(
         writer.send(wrap(BattleMessage::SetVelocity(current_velocity - 0.1)));
     }
     if keys.pressed(*player.key_map.switch_bullet.borrow()) {
-        let previous_bullet = player.selected_bullet.0.get_int_value();
-        let new_bullet = BulletType::get_from_int(previous_bullet + 1);
-        writer.send(wrap(BattleMessage::SelectBullet(new_bullet)));
+        if let Some(next) = weapons.next_owned(&player.selected_weapon, &player.inventory) {
+            writer.send(wrap(BattleMessage::SelectBullet(next)));
+        }
     }
 }
@@ -3,30 +3,54 @@ use core::f32;
 use bevy::{
     math::Vec2,
     prelude::*,
-    render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages},
     sprite::{MaterialMesh2dBundle, Mesh2dHandle},
-    utils::HashMap,
 };
 
 use bevy_iced::{IcedContext, IcedPlugin};
-use bullets::{Bullet, BulletCount, BulletType};
+use bullets::{
+    announce_projectiles, ballistic_step, dig_tunnels, rolling_step, split_clusters, start_rolling,
+    Bullet, PhysicsBody, TurnHold, BALLISTICS_RATE,
+};
+use effects::{
+    knock_back_tanks, play_explosion_animations, shake_camera, start_impact_effects, GifAnimation,
+    GifAnimationLoader, ScreenShake,
+};
 use inputs::handle_keypress;
-use tank::{Tank, TankBundle};
+use tank::{Tank, TankBundle, PARACHUTE_FALL_SPEED, TANK_GROUND_OFFSET};
+use terrain::{
+    fire::{burn_tanks, extinguish_fires, ignite_ground, rebuild_fire_meshes},
+    liquid::{bullets_in_liquid, drown_tanks, lava_damage, LiquidSurface},
+    map::{collect_maps, ImageMapLoader, MapAsset, MapLibrary, MapRules, RonMapLoader},
+    rebuild_terrain_mesh, reset_terrain, settle_terrain, Terrain, Wall,
+};
+use trajectory::draw_trajectory;
 use ui::{
     battle::{update_battle_ui, view_battle_ui, BattleMessage},
     shop::{update_shop_ui, view_shop_ui, ShopMessage},
     startmenu::{update_startmenu_ui, view_startmenu_ui, StartMenuMessage},
 };
 use utils::{
-    get_current_player_props, polynomial, random_wind, EndTurnEvent, FireEvent, GameMode,
-    GameState, Player, PlayerKillEvent, ResetEvent,
+    get_current_player_props, EndTurnEvent, ExplosionEvent, FireEvent, GameMode, GameState,
+    ImpactTarget, Player, PlayerKillEvent, PreviousPlayerState, ProjectileDespawned,
+    ProjectileImpact, ProjectileSpawned, ResetEvent,
 };
+use weapons::{
+    collect_weapons, equip_players, WeaponDefinition, WeaponId, WeaponLoader, WeaponRegistry,
+};
+use world::{apply_world_edges, fit_camera, WorldBounds};
 
 pub mod bullets;
+pub mod effects;
 pub mod inputs;
+#[cfg(feature = "physics")]
+pub mod physics;
 pub mod tank;
+pub mod terrain;
+pub mod trajectory;
 pub mod ui;
 pub mod utils;
+pub mod weapons;
+pub mod world;
 
 #[derive(Event, Clone)]
 pub enum UiMessage {
@@ -37,32 +61,108 @@ pub enum UiMessage {
 }
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins)
         .add_plugins(IcedPlugin::default())
         .add_event::<UiMessage>()
         .add_event::<FireEvent>()
+        .add_event::<ExplosionEvent>()
         .add_event::<EndTurnEvent>()
         .add_event::<ResetEvent>()
         .add_event::<PlayerKillEvent>()
+        .add_event::<ProjectileSpawned>()
+        .add_event::<ProjectileImpact>()
+        .add_event::<ProjectileDespawned>()
         .insert_resource::<GameState>(GameState::default())
+        .init_resource::<MapRules>()
+        .init_resource::<WorldBounds>()
+        .init_resource::<ScreenShake>()
+        .insert_resource(Time::<Fixed>::from_hz(BALLISTICS_RATE))
+        .init_asset::<MapAsset>()
+        .register_asset_loader(RonMapLoader)
+        .register_asset_loader(ImageMapLoader)
+        .init_asset::<WeaponDefinition>()
+        .register_asset_loader(WeaponLoader)
+        .init_asset::<GifAnimation>()
+        .register_asset_loader(GifAnimationLoader)
         .add_systems(Startup, setup)
         .add_systems(Update, update_ui)
         .add_systems(Update, reset_players)
         .add_systems(Update, view_ui)
         .add_systems(Update, collision_handler)
-        .add_systems(Update, bullet_collision)
-        .add_systems(Update, gravity)
-        .add_systems(Update, move_bullets)
+        .add_systems(
+            FixedUpdate,
+            bullet_collision
+                .after(apply_world_edges)
+                .after(bullets_in_liquid)
+                .after(dig_tunnels),
+        )
+        .add_systems(
+            FixedUpdate,
+            dig_tunnels.after(apply_world_edges).before(settle_terrain),
+        )
+        .add_systems(
+            FixedUpdate,
+            ignite_ground
+                .after(bullet_collision)
+                .before(detonate_projectiles),
+        )
+        .add_systems(
+            FixedUpdate,
+            start_impact_effects
+                .after(bullet_collision)
+                .before(detonate_projectiles),
+        )
+        .add_systems(
+            FixedUpdate,
+            knock_back_tanks
+                .after(bullet_collision)
+                .before(detonate_projectiles),
+        )
+        .add_systems(FixedUpdate, detonate_projectiles.after(bullet_collision))
+        .add_systems(FixedUpdate, explosion_handler.after(detonate_projectiles))
+        .add_systems(FixedUpdate, announce_projectiles.before(move_bullets))
+        .add_systems(
+            FixedUpdate,
+            split_clusters
+                .after(bullet_collision)
+                .before(detonate_projectiles),
+        )
+        .add_systems(
+            FixedUpdate,
+            resolve_turn
+                .after(explosion_handler)
+                .after(split_clusters)
+                .after(settle_terrain),
+        )
+        .add_systems(FixedUpdate, move_bullets)
+        .add_systems(FixedUpdate, apply_world_edges.after(move_bullets))
         .add_systems(Update, swap_player)
         .add_systems(Update, handle_keypress)
         .add_systems(Update, kill_handler)
-        .run();
+        .add_systems(Update, reset_terrain.before(reset_players))
+        .add_systems(Update, collect_maps)
+        .add_systems(Update, collect_weapons)
+        .add_systems(Update, equip_players.after(collect_weapons))
+        .add_systems(FixedUpdate, settle_terrain.before(bullet_collision))
+        .add_systems(Update, rebuild_terrain_mesh)
+        .add_systems(FixedUpdate, bullets_in_liquid.after(move_bullets))
+        .add_systems(Update, lava_damage)
+        .add_systems(Update, drown_tanks)
+        .add_systems(Update, burn_tanks.after(swap_player))
+        .add_systems(Update, rebuild_fire_meshes)
+        .add_systems(Update, extinguish_fires)
+        .add_systems(Update, fit_camera)
+        .add_systems(Update, draw_trajectory)
+        .add_systems(Update, play_explosion_animations)
+        .add_systems(Update, shake_camera.after(fit_camera));
+    #[cfg(not(feature = "physics"))]
+    app.add_systems(Update, gravity);
+    #[cfg(feature = "physics")]
+    app.add_plugins(physics::PhysicsBackendPlugin);
+    app.run();
 }
 
-#[derive(Component)]
-struct Wall {}
-
 #[allow(clippy::too_many_arguments)]
 pub fn update_ui(
     mut messages: EventReader<UiMessage>,
@@ -75,6 +175,9 @@ pub fn update_ui(
     reset_writer: EventWriter<ResetEvent>,
     end_turn_writer: EventWriter<EndTurnEvent>,
     asset_server: Res<AssetServer>,
+    terrain: Res<Terrain>,
+    bounds: ResMut<WorldBounds>,
+    weapons: Res<WeaponRegistry>,
 ) {
     let mut new_messages = messages.read().peekable();
     if let Some(UiMessage::SetSceneMessage(mode)) = new_messages.peek() {
@@ -92,21 +195,35 @@ pub fn update_ui(
             state,
             reset_writer,
             asset_server,
+            terrain,
+            weapons,
         ),
-        utils::GameMode::Shop => update_shop_ui(new_messages, state, query, end_turn_writer),
-        utils::GameMode::StartMenu => update_startmenu_ui(new_messages, state, reset_writer),
+        utils::GameMode::Shop => {
+            update_shop_ui(new_messages, state, query, end_turn_writer, weapons)
+        }
+        utils::GameMode::StartMenu => {
+            update_startmenu_ui(new_messages, state, bounds, reset_writer)
+        }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn view_ui(
     state: Res<GameState>,
     player_query: Query<(&Player, &Tank)>,
+    maps: Res<MapLibrary>,
+    map_assets: Res<Assets<MapAsset>>,
+    asset_server: Res<AssetServer>,
+    bounds: Res<WorldBounds>,
+    weapons: Res<WeaponRegistry>,
     ctx: IcedContext<UiMessage>,
 ) {
     match state.mode {
-        utils::GameMode::Battle => view_battle_ui(state, player_query, ctx),
-        utils::GameMode::Shop => view_shop_ui(state, player_query, ctx),
-        utils::GameMode::StartMenu => view_startmenu_ui(state, ctx),
+        utils::GameMode::Battle => view_battle_ui(state, player_query, weapons, ctx),
+        utils::GameMode::Shop => view_shop_ui(state, player_query, weapons, ctx),
+        utils::GameMode::StartMenu => {
+            view_startmenu_ui(state, maps, map_assets, asset_server, bounds, ctx)
+        }
     }
 }
 
@@ -114,28 +231,14 @@ fn setup(
     mut materials: ResMut<Assets<ColorMaterial>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut commands: Commands,
+    bounds: Res<WorldBounds>,
+    asset_server: Res<AssetServer>,
     mut writer: EventWriter<ResetEvent>,
 ) {
-    let rand: f32 = 0.5;
-    let mut vertices = Vec::new();
-    let mut i = -1920;
-    // TODO make this use a proper curve
-    for _ in -1920..1920 {
-        vertices.push([i as f32, 0.0, 0.0]);
-        let two = [i as f32, polynomial(i, rand), 0.0];
-        let three = [(i + 1) as f32, 0.0, 0.0];
-        vertices.push(two);
-        vertices.push(three);
-        vertices.push(three);
-        vertices.push(two);
-        vertices.push([(i + 1) as f32, polynomial(i + 1, rand), 0.0]);
-        i += 1;
-    }
-    let poly = Mesh::new(
-        PrimitiveTopology::TriangleList,
-        RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
-    )
-    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    // the ground of the selected map is filled in by `reset_terrain` once it is loaded
+    let terrain = Terrain::empty(bounds.left as i32, bounds.right as i32, bounds.bottom);
+    let poly = terrain.build_mesh();
+    let liquid = terrain.build_liquid_mesh();
 
     //poly.insert_indices(Indices::U32(indices));
     commands.spawn(Camera2dBundle::default());
@@ -143,11 +246,11 @@ fn setup(
     commands.spawn((
         MaterialMesh2dBundle {
             mesh: Mesh2dHandle(meshes.add(poly)),
-            material: materials.add(Color::BLACK),
+            material: materials.add(Color::WHITE),
             transform: Transform {
                 translation: Vec3 {
                     x: 0.0,
-                    y: -650.0,
+                    y: terrain.base,
                     z: 0.0,
                 },
                 ..default()
@@ -156,25 +259,55 @@ fn setup(
         },
         Wall {},
     ));
+    commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: Mesh2dHandle(meshes.add(liquid)),
+            material: materials.add(Color::WHITE),
+            transform: Transform {
+                translation: Vec3 {
+                    x: 0.0,
+                    y: terrain.base,
+                    // in front of tanks so sunken tanks are covered
+                    z: 2.0,
+                },
+                ..default()
+            },
+            ..default()
+        },
+        LiquidSurface {},
+    ));
+    commands.insert_resource(terrain);
+    commands.insert_resource(MapLibrary::new(asset_server.load_folder("maps")));
+    commands.insert_resource(WeaponRegistry::new(asset_server.load_folder("weapons")));
     writer.send(ResetEvent {});
 }
 
+#[allow(clippy::too_many_arguments)]
 fn reset_players(
     mut state: ResMut<GameState>,
+    rules: Res<MapRules>,
+    terrain: Res<Terrain>,
+    bounds: Res<WorldBounds>,
+    weapons: Res<WeaponRegistry>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     query: Query<(Entity, &Player)>,
     mut reader: EventReader<ResetEvent>,
 ) {
     if reader.read().next().is_some() {
-        state.wind = random_wind();
+        state.wind = rules.random_wind();
         state.active_player = 0;
-        let mut previous_player_states = Vec::<(u32, HashMap<BulletType, BulletCount>)>::new();
+        let mut previous_player_states = Vec::<PreviousPlayerState>::new();
         for (entity, player) in query.iter() {
-            previous_player_states.push((player.money, player.inventory.clone()));
+            previous_player_states.push((
+                player.money,
+                player.inventory.clone(),
+                player.parachutes,
+            ));
             commands.entity(entity).despawn_recursive();
         }
         for i in 0..state.player_count {
+            let spawn_x = terrain.nearest_dry_x(rules.spawn_point(i, state.player_count, &bounds));
             commands.spawn(TankBundle {
                 sprite: SpriteBundle {
                     texture: asset_server.load("greentank_rechts.png"),
@@ -185,8 +318,8 @@ fn reset_players(
                             z: 1.0,
                         },
                         translation: Vec3 {
-                            x: -200.0 + i as f32 * 150.0,
-                            y: -300.0,
+                            x: spawn_x,
+                            y: terrain.height_at(spawn_x) + TANK_GROUND_OFFSET,
                             z: 1.0,
                         },
                         ..default()
@@ -203,59 +336,130 @@ fn reset_players(
                     // top right
                     shooting_direction: tank::Angle::default(),
                     shooting_velocity: Vec2::new(1.0, 1.0),
+                    vertical_velocity: 0.0,
+                    horizontal_velocity: 0.0,
+                    fall_start: None,
+                    parachute_open: false,
+                    undermined_by: None,
                 },
-                player: Player::from_previous_or_initial(i, previous_player_states.get(i as usize)),
+                player: Player::from_previous_or_initial(
+                    i,
+                    previous_player_states.get(i as usize),
+                    &weapons,
+                ),
             });
         }
     }
 }
 
+/// Integrates projectile motion in `FixedUpdate`, so trajectories are the same on every machine.
 fn move_bullets(
     time: Res<Time>,
     state: Res<GameState>,
-    mut query: Query<(&mut Bullet, &mut Transform)>,
+    rules: Res<MapRules>,
+    terrain: Res<Terrain>,
+    mut query: Query<(&mut Bullet, &mut Transform), Without<PhysicsBody>>,
 ) {
     let delta = time.delta_seconds();
     for (mut bullet, mut transform) in &mut query {
-        let wind = state.wind;
-
-        // s0 + v0 + 0.5 * a * t * t
-        // calculate next positions
-
-        // y
-        transform.translation.y =
-            transform.translation.y + bullet.velocity_shot.y + 0.5 * -5.0 * delta * delta;
-        bullet.velocity_shot.y += delta * -5.0;
-
-        // x
-        transform.translation.x =
-            transform.translation.x + bullet.velocity_shot.x + 0.5 * wind * delta * delta;
-        //if bullet.velocity_shot.x > 0.0 {
-        //    bullet.velocity_shot.x = (/*delta * wind +*/bullet.velocity_shot.x).clamp(0.0, 1000.0);
-        //} else {
-        //    bullet.velocity_shot.x = (/*delta * wind +*/bullet.velocity_shot.x).clamp(-1000.0, 0.0);
-        //}
-
-        // TODO do we want air resistance?
-        //if bullet.velocity_shot.x > 0.0 {
-        //transform.translation.x =
-        //    transform.translation.x + bullet.velocity_shot.x + 0.5 * wind * delta * delta;
-        //bullet.velocity_shot.x = (delta * wind + bullet.velocity_shot.x).clamp(0.0, 1000.0);
-        //} else {
-        //    transform.translation.x = transform.translation.x
-        //        + bullet.velocity_shot.x
-        //        + 0.5 * 0.1/*TODO implement wind*/ * delta * delta;
-        //    bullet.velocity_shot.x = (delta * 0.1 + bullet.velocity_shot.x).clamp(-1000.0, 0.0);
-        //}
+        let position = transform.translation.truncate();
+        let friction = bullet.roll_friction();
+        let rolled = match friction {
+            Some(friction) if bullet.rolling => rolling_step(
+                &terrain,
+                position,
+                bullet.velocity_shot,
+                friction,
+                rules.gravity,
+                delta,
+            ),
+            _ => None,
+        };
+        // rollers leaving an edge fly on until they touch the ground again
+        bullet.rolling = rolled.is_some();
+        let (next_position, velocity) = match rolled {
+            Some(step) => step,
+            // diggers keep their direction and speed underground
+            None if bullet.digging => (
+                position + bullet.velocity_shot * delta,
+                bullet.velocity_shot,
+            ),
+            None => {
+                let (next_position, velocity) = ballistic_step(
+                    position,
+                    bullet.velocity_shot,
+                    bullet.exposure,
+                    state.wind,
+                    rules.gravity,
+                    delta,
+                );
+                // rollers switch to rolling on their first ground contact, diggers start
+                // burrowing in `dig_tunnels`
+                match terrain.raycast(position, next_position) {
+                    Some(contact) if friction.is_some() => {
+                        bullet.rolling = true;
+                        start_rolling(&terrain, contact, velocity)
+                    }
+                    _ => (next_position, velocity),
+                }
+            }
+        };
+        bullet.previous_position = position;
+        bullet.velocity_shot = velocity;
+        bullet.risen |= velocity.y > 0.0;
+        bullet.flight_time += delta;
+        transform.translation = next_position.extend(transform.translation.z);
     }
 }
 
-fn gravity(mut query: Query<(&Tank, &mut Transform)>) {
-    for (_, mut transform) in &mut query {
-        transform.translation.y = (transform.translation.y - 9.81).clamp(
-            polynomial(transform.translation.x as i32, 0.5) - 550.0,
-            1000.0,
-        );
+#[cfg_attr(feature = "physics", allow(dead_code))]
+fn gravity(
+    time: Res<Time>,
+    terrain: Res<Terrain>,
+    rules: Res<MapRules>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Player, &mut Tank, &mut Transform)>,
+    mut kill_writer: EventWriter<PlayerKillEvent>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut player, mut tank, mut transform) in &mut query {
+        let ground = terrain.height_at(transform.translation.x) + TANK_GROUND_OFFSET;
+        if transform.translation.y > ground || tank.vertical_velocity > 0.0 {
+            tank.fall(&mut player, transform.translation.y, ground);
+            tank.vertical_velocity -= rules.tank_gravity() * delta;
+            if tank.parachute_open {
+                tank.vertical_velocity = tank.vertical_velocity.max(-PARACHUTE_FALL_SPEED);
+            }
+            transform.translation.y += tank.vertical_velocity * delta;
+        }
+        if tank.horizontal_velocity != 0.0 {
+            let target = transform.translation.x + tank.horizontal_velocity * delta;
+            if tank.hits_wall(target, transform.translation.y, &terrain) {
+                tank.horizontal_velocity = 0.0;
+            } else {
+                transform.translation.x = target;
+            }
+        }
+        let ground = terrain.height_at(transform.translation.x) + TANK_GROUND_OFFSET;
+        if transform.translation.y <= ground {
+            transform.translation.y = ground;
+            transform.rotation = Quat::from_rotation_z(
+                terrain.slope_angle_at(transform.translation.x, tank.scale.x),
+            );
+            let undermined_by = tank.undermined_by;
+            let damage = tank.touch_down(&terrain, transform.translation.x, ground);
+            if damage == 0 {
+                continue;
+            }
+            player.health -= damage;
+            if player.health < 0 {
+                kill_writer.send(PlayerKillEvent {
+                    killer: undermined_by.unwrap_or(player.player_number),
+                    killed: player.player_number,
+                });
+                commands.entity(entity).despawn_recursive();
+            }
+        }
     }
 }
 
@@ -275,44 +479,165 @@ fn collision_handler(
     }
 }
 
-fn bullet_collision(
-    mut commands: Commands,
+/// Ends the turn once every projectile, including split sub-munitions, and every `TurnHold` is
+/// gone and all explosions have settled. Runs last in `FixedUpdate`, so spawns and despawns of
+/// this step are already applied.
+fn resolve_turn(
     mut state: ResMut<GameState>,
-    bullets: Query<(Entity, &mut Bullet, &Transform)>,
-    walls: Query<(&Wall, &Transform)>,
-    mut query: Query<(Entity, &mut Player, &Tank, &Transform)>,
+    terrain: Res<Terrain>,
+    bullets: Query<(), With<Bullet>>,
+    holds: Query<(), With<TurnHold>>,
+    tanks: Query<&Tank>,
+    mut explosions: EventReader<ExplosionEvent>,
     mut writer: EventWriter<EndTurnEvent>,
-    mut battle_writer: EventWriter<PlayerKillEvent>,
 ) {
-    if bullets.iter().len() == 0 && state.firing {
+    let exploded = explosions.read().count() > 0;
+    if state.firing
+        && bullets.is_empty()
+        && holds.is_empty()
+        && !exploded
+        && terrain.is_settled()
+        && !tanks.iter().any(|tank| tank.is_falling())
+    {
         state.firing = false;
         writer.send(EndTurnEvent {});
     }
-    for (entity, bullet, bullet_transform) in &bullets {
-        for (_, _) in &walls {
-            if bullet_transform.translation.y
-                < polynomial(bullet_transform.translation.x as i32, 0.5) - 650.0
-            {
-                commands.entity(entity).despawn_recursive();
-            }
+}
+
+/// Sends a `ProjectileImpact` for every projectile whose path of this step hit a tank or the
+/// terrain, or whose fuse ran out or cluster trigger fired.
+fn bullet_collision(
+    terrain: Res<Terrain>,
+    bullets: Query<(Entity, &Bullet, &WeaponId, &Transform)>,
+    query: Query<(Entity, &Tank, &Transform)>,
+    mut impact_writer: EventWriter<ProjectileImpact>,
+) {
+    for (entity, bullet, weapon, bullet_transform) in &bullets {
+        let from = bullet.previous_position;
+        let to = bullet_transform.translation.xy();
+        let tank_hits = query
+            .iter()
+            .filter_map(|(tank_entity, tank, tank_transform)| {
+                let position = tank.hit_along(tank_transform, from, to)?;
+                let normal = (position - tank_transform.translation.xy()).normalize_or_zero();
+                Some((position, normal, ImpactTarget::Tank(tank_entity)))
+            });
+        // rolling and digging projectiles move along or through the ground
+        let terrain_hit = if bullet.rolling || bullet.digging {
+            None
+        } else {
+            terrain.raycast(from, to)
+        };
+        let terrain_hit = terrain_hit.map(|position| {
+            (
+                position,
+                terrain.normal_at(position.x),
+                ImpactTarget::Terrain,
+            )
+        });
+        let impact = tank_hits
+            .chain(terrain_hit)
+            .min_by(|a, b| from.distance(a.0).total_cmp(&from.distance(b.0)))
+            .or_else(|| {
+                (bullet.fuse_expired() || bullet.cluster_split().is_some()).then(|| {
+                    let normal = -bullet.velocity_shot.normalize_or_zero();
+                    (to, normal, ImpactTarget::Air)
+                })
+            })
+            .or_else(|| {
+                bullet
+                    .at_rest()
+                    .then(|| (to, terrain.normal_at(to.x), ImpactTarget::Terrain))
+            })
+            .or_else(|| {
+                bullet.tunnel_finished().then(|| {
+                    let normal = -bullet.velocity_shot.normalize_or_zero();
+                    (to, normal, ImpactTarget::Terrain)
+                })
+            });
+        if let Some((position, normal, target)) = impact {
+            impact_writer.send(ProjectileImpact {
+                entity,
+                weapon: weapon.clone(),
+                owner: bullet.owner,
+                position,
+                normal,
+                target,
+            });
         }
-        for (tank_entity, mut player, tank, tank_transform) in &mut query {
-            if bullet_transform.translation.y <= tank_transform.translation.y + (tank.scale.y / 2.0)
-                && bullet_transform.translation.y
-                    >= tank_transform.translation.y - (tank.scale.y / 2.0)
-                && bullet_transform.translation.x
-                    <= tank_transform.translation.x + (tank.scale.x / 2.0)
-                && bullet_transform.translation.x
-                    >= tank_transform.translation.x - (tank.scale.x / 2.0)
-            {
-                player.health -= bullet.damage as i32;
-                if player.health < 0 {
-                    battle_writer.send(PlayerKillEvent {
-                        killer: bullet.owner,
-                        killed: player.player_number,
-                    });
-                    commands.entity(tank_entity).despawn_recursive();
-                }
+    }
+}
+
+/// Default impact handler, explodes every projectile that hit something and removes it. Cluster
+/// carriers splitting in the air are removed without an explosion. Weapon specific handlers read
+/// the same `ProjectileImpact` events before this runs.
+fn detonate_projectiles(
+    mut commands: Commands,
+    bullets: Query<&Bullet>,
+    mut reader: EventReader<ProjectileImpact>,
+    mut explosion_writer: EventWriter<ExplosionEvent>,
+    mut despawn_writer: EventWriter<ProjectileDespawned>,
+) {
+    for impact in reader.read() {
+        let bullet = if let Ok(bullet) = bullets.get(impact.entity) {
+            bullet
+        } else {
+            continue;
+        };
+        // the carrier of a cluster only releases its sub-munitions, `split_clusters` spawns them
+        let split = impact.target == ImpactTarget::Air && bullet.cluster_split().is_some();
+        if !split {
+            explosion_writer.send(ExplosionEvent {
+                position: impact.position,
+                radius: bullet.radius,
+                damage: bullet.damage,
+                falloff: bullet.falloff,
+                owner: bullet.owner,
+            });
+        }
+        despawn_writer.send(ProjectileDespawned {
+            entity: impact.entity,
+            weapon: impact.weapon.clone(),
+            owner: impact.owner,
+            position: impact.position,
+        });
+        commands.entity(impact.entity).despawn_recursive();
+    }
+}
+
+/// Carves the terrain and damages every tank in range once per explosion.
+fn explosion_handler(
+    mut commands: Commands,
+    mut terrain: ResMut<Terrain>,
+    mut reader: EventReader<ExplosionEvent>,
+    mut query: Query<(Entity, &mut Player, &mut Tank, &Transform)>,
+    mut kill_writer: EventWriter<PlayerKillEvent>,
+) {
+    for explosion in reader.read() {
+        terrain.carve(explosion.position, explosion.radius);
+        for (entity, mut player, mut tank, transform) in &mut query {
+            if player.health < 0 {
+                continue;
+            }
+            tank.undermine(
+                transform,
+                explosion.position,
+                explosion.radius,
+                explosion.owner,
+            );
+            let distance = tank.distance_to(transform, explosion.position);
+            let factor = explosion.falloff.factor(distance, explosion.radius);
+            let damage = (explosion.damage as f32 * factor).round() as i32;
+            if damage == 0 {
+                continue;
+            }
+            player.health -= damage;
+            if player.health < 0 {
+                kill_writer.send(PlayerKillEvent {
+                    killer: explosion.owner,
+                    killed: player.player_number,
+                });
+                commands.entity(entity).despawn_recursive();
             }
         }
     }
@@ -332,6 +657,7 @@ fn kill_handler(mut reader: EventReader<PlayerKillEvent>, mut players: Query<&mu
 
 fn swap_player(
     mut state: ResMut<GameState>,
+    rules: Res<MapRules>,
     mut reader: EventReader<EndTurnEvent>,
     mut ui_writer: EventWriter<UiMessage>,
     mut reset_writer: EventWriter<ResetEvent>,
@@ -343,7 +669,7 @@ fn swap_player(
         state.firing = false;
     }
     for _ in reader.read() {
-        state.wind = random_wind();
+        state.wind = rules.random_wind();
         let (_, player, _, _, _) =
             if let Some(props) = get_current_player_props(state.active_player, &mut players) {
                 props
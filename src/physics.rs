@@ -0,0 +1,338 @@
+use avian2d::prelude::{
+    AngularDamping, CoefficientCombine, Collider, CollisionLayers, Friction, Gravity, GravityScale,
+    LayerMask, LinearVelocity, LockedAxes, PhysicsPlugins, Restitution, RigidBody,
+};
+use bevy::{
+    asset::Assets,
+    math::Vec2,
+    prelude::{
+        default, App, Commands, Component, DespawnRecursiveExt, DetectChanges, Entity, EventReader,
+        EventWriter, FixedUpdate, IntoSystemConfigs, Mesh, Plugin, Query, Rectangle, Res, ResMut,
+        Transform, TransformBundle, Update, With, Without,
+    },
+    sprite::{ColorMaterial, MaterialMesh2dBundle, Mesh2dHandle},
+    time::Time,
+};
+use rand::Rng;
+
+use crate::{
+    bullets::{Bullet, PhysicsBody, ROLL_CLEARANCE},
+    effects::knockback_airtime,
+    move_bullets,
+    tank::{Tank, PARACHUTE_FALL_SPEED, TANK_GRAVITY, TANK_GROUND_OFFSET},
+    terrain::map::MapRules,
+    terrain::{liquid::bullets_in_liquid, Terrain, COLUMN_WIDTH},
+    utils::{ExplosionEvent, Player, PlayerKillEvent},
+    world::apply_world_edges,
+};
+
+/// Number of terrain columns between two points of the terrain collider.
+const TERRAIN_COLLIDER_STEP: f32 = 4.0;
+/// Tanks moving down faster than this are considered falling.
+const FALLING_SPEED: f32 = 20.0;
+const TANK_ANGULAR_DAMPING: f32 = 5.0;
+/// Rolling projectiles slower than this come to rest.
+const ROLLER_REST_SPEED: f32 = 5.0;
+/// Debris chunks thrown out per world unit of explosion radius.
+const DEBRIS_PER_RADIUS: f32 = 0.2;
+const MAX_DEBRIS: usize = 12;
+const DEBRIS_SIZE: f32 = 3.0;
+const DEBRIS_SPEED: f32 = 300.0;
+/// Seconds before debris is removed again.
+const DEBRIS_LIFETIME: f32 = 2.0;
+
+// Projectiles and debris only collide with the terrain, so they never push tanks around.
+const TERRAIN_LAYER: u32 = 0b0001;
+const TANK_LAYER: u32 = 0b0010;
+const PROJECTILE_LAYER: u32 = 0b0100;
+const DEBRIS_LAYER: u32 = 0b1000;
+
+/// Simulates tanks, rolling projectiles and debris thrown out of craters with avian2d instead of
+/// the hand written `gravity` system and `rolling_step`. Flying projectiles keep using
+/// `move_bullets` and are handed over once they start rolling.
+pub struct PhysicsBackendPlugin;
+
+impl Plugin for PhysicsBackendPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(PhysicsPlugins::default())
+            .insert_resource(Gravity(Vec2::NEG_Y * TANK_GRAVITY))
+            .add_systems(Update, add_tank_bodies)
+            .add_systems(Update, apply_map_gravity)
+            .add_systems(Update, rebuild_terrain_collider)
+            .add_systems(Update, apply_knockbacks.before(track_tank_falls))
+            .add_systems(Update, track_tank_falls)
+            .add_systems(FixedUpdate, add_roller_bodies.after(move_bullets))
+            .add_systems(
+                FixedUpdate,
+                read_projectile_bodies
+                    .after(move_bullets)
+                    .before(apply_world_edges)
+                    .before(bullets_in_liquid),
+            )
+            .add_systems(
+                FixedUpdate,
+                write_projectile_bodies
+                    .after(apply_world_edges)
+                    .after(bullets_in_liquid),
+            )
+            .add_systems(Update, spawn_debris)
+            .add_systems(Update, remove_debris);
+    }
+}
+
+#[derive(Component)]
+pub struct TerrainBody {}
+
+/// Chunk of ground thrown out of a crater.
+#[derive(Component)]
+pub struct Debris {
+    age: f32,
+}
+
+fn add_tank_bodies(
+    mut commands: Commands,
+    tanks: Query<(Entity, &Tank, &Transform), Without<RigidBody>>,
+) {
+    for (entity, tank, transform) in &tanks {
+        // colliders are scaled with the sprite, the box reaches from the ground to the top of the
+        // tank
+        commands.entity(entity).insert((
+            RigidBody::Dynamic,
+            Collider::rectangle(
+                tank.scale.x / transform.scale.x,
+                2.0 * TANK_GROUND_OFFSET / transform.scale.y,
+            ),
+            AngularDamping(TANK_ANGULAR_DAMPING),
+            CollisionLayers::new(TANK_LAYER, TERRAIN_LAYER | TANK_LAYER),
+        ));
+    }
+}
+
+/// Replaces the static terrain collider with the current surface whenever the terrain changes.
+fn rebuild_terrain_collider(
+    mut commands: Commands,
+    terrain: Res<Terrain>,
+    bodies: Query<Entity, With<TerrainBody>>,
+) {
+    if !terrain.is_changed() {
+        return;
+    }
+    let step = TERRAIN_COLLIDER_STEP * COLUMN_WIDTH;
+    let points = ((terrain.right() - terrain.left) / step) as usize + 1;
+    let surface = (0..=points)
+        .map(|index| {
+            let x = (terrain.left + index as f32 * step).min(terrain.right());
+            Vec2::new(x, terrain.height_at(x))
+        })
+        .collect();
+    let collider = Collider::polyline(surface, None);
+    match bodies.get_single() {
+        Ok(entity) => {
+            commands.entity(entity).insert(collider);
+        }
+        Err(_) => {
+            commands.spawn((
+                RigidBody::Static,
+                collider,
+                CollisionLayers::new(TERRAIN_LAYER, LayerMask::ALL),
+                // friction is multiplied with the coefficient of the other body, keeping its own
+                Friction::new(1.0).with_combine_rule(CoefficientCombine::Multiply),
+                TransformBundle::default(),
+                TerrainBody {},
+            ));
+        }
+    }
+}
+
+/// Lets tanks fall with the gravity of the current map.
+fn apply_map_gravity(rules: Res<MapRules>, mut gravity: ResMut<Gravity>) {
+    if rules.is_changed() {
+        gravity.0 = Vec2::NEG_Y * rules.tank_gravity();
+    }
+}
+
+/// Hands the launch velocity set by `knock_back_tanks` to the physics engine. The sideways part
+/// is dropped if a driving tank couldn't reach the spot the tank would land on.
+fn apply_knockbacks(
+    terrain: Res<Terrain>,
+    rules: Res<MapRules>,
+    mut tanks: Query<(Entity, &mut Tank, &Transform, &mut LinearVelocity)>,
+) {
+    let positions: Vec<(Entity, f32)> = tanks
+        .iter()
+        .map(|(entity, _, transform, _)| (entity, transform.translation.x))
+        .collect();
+    for (entity, mut tank, transform, mut velocity) in &mut tanks {
+        if tank.horizontal_velocity == 0.0 && tank.vertical_velocity <= 0.0 {
+            continue;
+        }
+        let x = transform.translation.x;
+        let airtime = knockback_airtime(tank.vertical_velocity.max(0.0), rules.tank_gravity());
+        let landing = x + tank.horizontal_velocity * airtime;
+        let other_tanks: Vec<f32> = positions
+            .iter()
+            .filter(|(other, _)| *other != entity)
+            .map(|(_, other_x)| *other_x)
+            .collect();
+        if !tank.blocked(x, landing, &terrain, &other_tanks) {
+            velocity.x += tank.horizontal_velocity;
+        }
+        velocity.y += tank.vertical_velocity.max(0.0);
+        tank.horizontal_velocity = 0.0;
+        tank.vertical_velocity = 0.0;
+    }
+}
+
+/// Applies the parachute and fall damage rules of the hand written `gravity` system to tanks
+/// moved by the physics engine.
+fn track_tank_falls(
+    mut commands: Commands,
+    terrain: Res<Terrain>,
+    mut tanks: Query<(
+        Entity,
+        &mut Player,
+        &mut Tank,
+        &Transform,
+        &mut LinearVelocity,
+    )>,
+    mut kill_writer: EventWriter<PlayerKillEvent>,
+) {
+    for (entity, mut player, mut tank, transform, mut velocity) in &mut tanks {
+        let y = transform.translation.y;
+        if velocity.y < -FALLING_SPEED {
+            let ground = terrain.height_at(transform.translation.x) + TANK_GROUND_OFFSET;
+            tank.fall(&mut player, y, ground);
+            if tank.parachute_open {
+                velocity.y = velocity.y.max(-PARACHUTE_FALL_SPEED);
+            }
+            continue;
+        }
+        if !tank.is_falling() {
+            continue;
+        }
+        let undermined_by = tank.undermined_by;
+        let damage = tank.touch_down(&terrain, transform.translation.x, y);
+        if damage == 0 {
+            continue;
+        }
+        player.health -= damage;
+        if player.health < 0 {
+            kill_writer.send(PlayerKillEvent {
+                killer: undermined_by.unwrap_or(player.player_number),
+                killed: player.player_number,
+            });
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Hands projectiles that started rolling over to the physics engine. Sliding friction on the
+/// terrain slows them down by the `WeaponBehavior::Roll` friction on flat ground.
+fn add_roller_bodies(
+    mut commands: Commands,
+    rules: Res<MapRules>,
+    bullets: Query<(Entity, &Bullet), Without<PhysicsBody>>,
+) {
+    for (entity, bullet) in &bullets {
+        let friction = match bullet.roll_friction() {
+            Some(friction) if bullet.rolling => friction,
+            _ => continue,
+        };
+        commands.entity(entity).insert((
+            RigidBody::Dynamic,
+            Collider::circle(ROLL_CLEARANCE),
+            CollisionLayers::new(PROJECTILE_LAYER, TERRAIN_LAYER),
+            LinearVelocity(bullet.velocity_shot),
+            // the engine pulls with the tank gravity, projectiles fall with the map gravity
+            GravityScale(rules.gravity / rules.tank_gravity()),
+            // sliding friction decelerates by its coefficient times gravity
+            Friction::new(friction / rules.gravity),
+            Restitution::ZERO,
+            LockedAxes::ROTATION_LOCKED,
+            PhysicsBody {},
+        ));
+    }
+}
+
+/// Copies the motion of projectile bodies into their `Bullet`, so impacts, world edges and
+/// liquids treat them like projectiles moved by `move_bullets`.
+fn read_projectile_bodies(
+    time: Res<Time>,
+    mut bullets: Query<(&mut Bullet, &Transform, &LinearVelocity), With<PhysicsBody>>,
+) {
+    let delta = time.delta_seconds();
+    for (mut bullet, transform, velocity) in &mut bullets {
+        bullet.previous_position = transform.translation.truncate() - velocity.0 * delta;
+        bullet.velocity_shot = if velocity.length() < ROLLER_REST_SPEED {
+            Vec2::ZERO
+        } else {
+            velocity.0
+        };
+        bullet.flight_time += delta;
+    }
+}
+
+/// Hands velocity changes from world edges and liquid drag back to the physics engine.
+fn write_projectile_bodies(mut bullets: Query<(&Bullet, &mut LinearVelocity), With<PhysicsBody>>) {
+    for (bullet, mut velocity) in &mut bullets {
+        velocity.0 = bullet.velocity_shot;
+    }
+}
+
+/// Throws chunks of ground out of craters, colored like the ground around them.
+fn spawn_debris(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    terrain: Res<Terrain>,
+    rules: Res<MapRules>,
+    mut reader: EventReader<ExplosionEvent>,
+) {
+    let mut rng = rand::thread_rng();
+    for explosion in reader.read() {
+        let surface = terrain.height_at(explosion.position.x);
+        if explosion.position.y - explosion.radius > surface {
+            continue;
+        }
+        let ground = Vec2::new(explosion.position.x, surface - 1.0);
+        let material = match terrain.material_at(ground) {
+            Some(material) if material.hardness() < 1.0 => material,
+            _ => continue,
+        };
+        let count = ((explosion.radius * DEBRIS_PER_RADIUS) as usize).min(MAX_DEBRIS);
+        let mesh = Mesh2dHandle(meshes.add(Rectangle::new(DEBRIS_SIZE, DEBRIS_SIZE)));
+        let color = materials.add(material.color());
+        for _ in 0..count {
+            let x =
+                explosion.position.x + rng.gen_range(-explosion.radius..=explosion.radius) / 2.0;
+            let direction = Vec2::from_angle(rng.gen_range(0.2..0.8) * std::f32::consts::PI);
+            commands.spawn((
+                MaterialMesh2dBundle {
+                    mesh: mesh.clone(),
+                    material: color.clone(),
+                    transform: Transform::from_xyz(x, terrain.height_at(x) + DEBRIS_SIZE, 0.0),
+                    ..default()
+                },
+                RigidBody::Dynamic,
+                Collider::rectangle(DEBRIS_SIZE, DEBRIS_SIZE),
+                CollisionLayers::new(DEBRIS_LAYER, TERRAIN_LAYER),
+                LinearVelocity(direction * DEBRIS_SPEED * rng.gen_range(0.5..1.0)),
+                GravityScale(rules.gravity / rules.tank_gravity()),
+                Debris { age: 0.0 },
+            ));
+        }
+    }
+}
+
+fn remove_debris(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut debris: Query<(Entity, &mut Debris)>,
+) {
+    for (entity, mut chunk) in &mut debris {
+        chunk.age += time.delta_seconds();
+        if chunk.age > DEBRIS_LIFETIME {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
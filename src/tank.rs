@@ -2,11 +2,34 @@ use core::f32;
 
 use bevy::{
     math::Vec2,
-    prelude::{Bundle, Component},
+    prelude::{Bundle, Component, Transform},
     sprite::SpriteBundle,
 };
 
-use crate::utils::Player;
+use crate::{terrain::Terrain, utils::Player};
+
+/// Distance between the ground and the center of a tank sprite.
+pub const TANK_GROUND_OFFSET: f32 = 100.0;
+/// Downward acceleration of tanks in world units per second squared.
+pub const TANK_GRAVITY: f32 = 1200.0;
+/// Maximum falling speed while a parachute is open.
+pub const PARACHUTE_FALL_SPEED: f32 = 100.0;
+/// Falls shorter than this don't deal damage.
+pub const SAFE_FALL_DISTANCE: f32 = 20.0;
+/// Damage dealt per world unit fallen beyond `SAFE_FALL_DISTANCE`.
+pub const FALL_DAMAGE_PER_UNIT: f32 = 0.2;
+/// Parachutes are only used up for falls that would deal at least this much damage.
+pub const PARACHUTE_MIN_DAMAGE: i32 = 10;
+/// Horizontal driving speed in world units per second.
+pub const DRIVE_SPEED: f32 = 100.0;
+/// Fuel burned per world unit driven on flat ground.
+pub const FUEL_PER_UNIT: f32 = 1.0;
+/// Additional fuel factor per unit of uphill grade.
+pub const UPHILL_FUEL_FACTOR: f32 = 3.0;
+/// Steepest slope in radians a tank can still climb.
+pub const MAX_CLIMB_ANGLE: f32 = 0.8;
+pub const PARACHUTE_COST: u32 = 50;
+pub const MAX_PARACHUTES: u32 = 5;
 
 #[derive(Clone, Copy)]
 pub struct Angle {
@@ -55,9 +78,139 @@ impl Angle {
 #[derive(Component, Clone)]
 pub struct Tank {
     pub blocked_direction: Vec2,
+    /// Barrel angle in world space, unaffected by the tank leaning on slopes.
     pub shooting_direction: Angle,
     pub shooting_velocity: Vec2,
     pub(crate) scale: bevy::prelude::Vec3,
+    /// Vertical speed in world units per second, negative while falling.
+    pub vertical_velocity: f32,
+    /// Sideways speed in world units per second of a tank thrown by a knockback, zero once it
+    /// lands or is blocked.
+    pub horizontal_velocity: f32,
+    /// Height the current fall started at, `None` while standing on the ground.
+    pub fall_start: Option<f32>,
+    pub parachute_open: bool,
+    /// Player whose explosion last removed ground below the tank since it last drove, credited
+    /// with deaths from falling, drowning or lava.
+    pub undermined_by: Option<u32>,
+}
+
+/// Damage for falling `distance` world units without a parachute.
+fn fall_damage(distance: f32) -> i32 {
+    ((distance - SAFE_FALL_DISTANCE).max(0.0) * FALL_DAMAGE_PER_UNIT) as i32
+}
+
+impl Tank {
+    /// Whether the world position lies inside the hitbox, which rotates with the sprite.
+    /// Returns the first point where the segment from `from` to `to` enters the rotated hitbox.
+    pub fn hit_along(&self, transform: &Transform, from: Vec2, to: Vec2) -> Option<Vec2> {
+        let to_local = |point: Vec2| {
+            (transform.rotation.inverse() * (point.extend(0.0) - transform.translation)).truncate()
+        };
+        let start = to_local(from);
+        let direction = to_local(to) - start;
+        let half_size = self.scale.truncate() / 2.0;
+        let mut enter: f32 = 0.0;
+        let mut exit: f32 = 1.0;
+        for axis in 0..2 {
+            if direction[axis] == 0.0 {
+                if start[axis].abs() > half_size[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let first = (-half_size[axis] - start[axis]) / direction[axis];
+            let second = (half_size[axis] - start[axis]) / direction[axis];
+            enter = enter.max(first.min(second));
+            exit = exit.min(first.max(second));
+        }
+        if enter <= exit {
+            Some(from + (to - from) * enter)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the distance from `point` to the closest point of the rotated hitbox.
+    pub fn distance_to(&self, transform: &Transform, point: Vec2) -> f32 {
+        let local = transform.rotation.inverse() * (point.extend(0.0) - transform.translation);
+        (local.truncate().abs() - self.scale.truncate() / 2.0)
+            .max(Vec2::ZERO)
+            .length()
+    }
+
+    /// Whether moving sideways from `x` to `target` is blocked by the map edges, another tank in
+    /// the way, liquid or a too steep slope. Tanks may leave liquid but never enter it.
+    pub fn blocked(&self, x: f32, target: f32, terrain: &Terrain, other_tanks: &[f32]) -> bool {
+        let half_width = self.scale.x / 2.0;
+        if target - half_width < terrain.left || target + half_width > terrain.right() {
+            return true;
+        }
+        let blocked_by_tank = other_tanks.iter().any(|other| {
+            (other - target).abs() < self.scale.x && (other - target).abs() < (other - x).abs()
+        });
+        let into_liquid =
+            terrain.liquid_depth_at(target).is_some() && terrain.liquid_depth_at(x).is_none();
+        let slope = terrain.slope_angle_at(target, self.scale.x) * (target - x).signum();
+        blocked_by_tank || into_liquid || slope > MAX_CLIMB_ANGLE
+    }
+
+    /// Whether a tank flying at height `y` would hit the map edge or the side of the terrain
+    /// when moving sideways to `target`. Unlike with `blocked`, slopes, liquid and other tanks don't
+    /// stop a tank in the air.
+    pub fn hits_wall(&self, target: f32, y: f32, terrain: &Terrain) -> bool {
+        let half_width = self.scale.x / 2.0;
+        target - half_width < terrain.left
+            || target + half_width > terrain.right()
+            || terrain.height_at(target) + TANK_GROUND_OFFSET > y
+    }
+
+    pub fn is_falling(&self) -> bool {
+        self.fall_start.is_some()
+    }
+
+    /// Remembers `owner` as the player who removed the ground if the tank stands within `radius`
+    /// of `center`.
+    pub fn undermine(&mut self, transform: &Transform, center: Vec2, radius: f32, owner: u32) {
+        if (transform.translation.x - center.x).abs() <= radius + self.scale.x / 2.0 {
+            self.undermined_by = Some(owner);
+        }
+    }
+
+    /// Remembers where the fall started at and opens a parachute, if the player owns one, once
+    /// landing on the `ground` below would deal at least `PARACHUTE_MIN_DAMAGE`.
+    pub fn fall(&mut self, player: &mut Player, y: f32, ground: f32) {
+        let fall_start = *self.fall_start.get_or_insert(y);
+        if !self.parachute_open
+            && player.parachutes > 0
+            && fall_damage(fall_start - ground) >= PARACHUTE_MIN_DAMAGE
+        {
+            player.parachutes -= 1;
+            self.parachute_open = true;
+        }
+    }
+
+    /// Returns the damage for standing on the ground at `ground` at the world x coordinate.
+    /// Liquid breaks the fall.
+    pub fn touch_down(&mut self, terrain: &Terrain, x: f32, ground: f32) -> i32 {
+        let landing_damage = self.land(ground);
+        if terrain.liquid_depth_at(x).is_some() {
+            0
+        } else {
+            landing_damage
+        }
+    }
+
+    /// Returns the fall damage for landing at `ground`, a deployed parachute negates it.
+    pub fn land(&mut self, ground: f32) -> i32 {
+        self.vertical_velocity = 0.0;
+        self.horizontal_velocity = 0.0;
+        let distance = self.fall_start.take().map_or(0.0, |start| start - ground);
+        if std::mem::take(&mut self.parachute_open) {
+            return 0;
+        }
+        fall_damage(distance)
+    }
 }
 
 #[derive(Bundle)]
@@ -0,0 +1,202 @@
+use bevy::{
+    asset::Assets,
+    color::{Color, ColorToComponents, LinearRgba},
+    math::Vec3,
+    prelude::{
+        default, Commands, Component, DespawnRecursiveExt, DetectChanges, Entity, EventReader,
+        EventWriter, Mesh, Query, Res, ResMut, Transform, With,
+    },
+    render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages},
+    sprite::{ColorMaterial, MaterialMesh2dBundle, Mesh2dHandle},
+};
+
+use crate::{
+    bullets::Bullet,
+    tank::Tank,
+    utils::{
+        damage_tank, EndTurnEvent, GameMode, GameState, ImpactTarget, Player, PlayerKillEvent,
+        ProjectileImpact, ResetEvent,
+    },
+    weapons::WeaponBehavior,
+};
+
+use super::Terrain;
+
+/// Height of the flames drawn above the burning surface.
+const FLAME_HEIGHT: f32 = 12.0;
+/// Horizontal distance between two points of the flame mesh.
+const FLAME_STEP: f32 = 4.0;
+const FLAME_COLOR: Color = Color::srgba(1.0, 0.45, 0.0, 0.8);
+
+/// Stretch of burning ground left behind by an `Ignite` projectile.
+#[derive(Component)]
+pub struct BurningGround {
+    pub left: f32,
+    pub right: f32,
+    /// Turns until the fire dies out.
+    pub turns_left: u32,
+    /// Damage dealt to a tank starting its turn inside the fire.
+    pub damage: i32,
+    /// Player credited with kills.
+    pub owner: u32,
+}
+
+impl BurningGround {
+    pub fn contains(&self, x: f32) -> bool {
+        x >= self.left && x <= self.right
+    }
+
+    /// Builds a band of flames following the current terrain surface.
+    pub fn build_mesh(&self, terrain: &Terrain) -> Mesh {
+        let color = LinearRgba::from(FLAME_COLOR).to_f32_array();
+        let mut vertices = Vec::new();
+        let mut x = self.left;
+        while x < self.right {
+            let next_x = (x + FLAME_STEP).min(self.right);
+            let ground = terrain.height_at(x);
+            let next_ground = terrain.height_at(next_x);
+            let top = [x, ground + FLAME_HEIGHT, 0.0];
+            let next_bottom = [next_x, next_ground, 0.0];
+            vertices.push([x, ground, 0.0]);
+            vertices.push(top);
+            vertices.push(next_bottom);
+            vertices.push(next_bottom);
+            vertices.push(top);
+            vertices.push([next_x, next_ground + FLAME_HEIGHT, 0.0]);
+            x = next_x;
+        }
+        let colors = vec![color; vertices.len()];
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    }
+}
+
+/// Impact handler setting the ground around projectiles with an `Ignite` behavior on fire.
+pub fn ignite_ground(
+    mut commands: Commands,
+    terrain: Res<Terrain>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    bullets: Query<&Bullet>,
+    mut reader: EventReader<ProjectileImpact>,
+) {
+    for impact in reader.read() {
+        if impact.target == ImpactTarget::Air {
+            continue;
+        }
+        let bullet = if let Ok(bullet) = bullets.get(impact.entity) {
+            bullet
+        } else {
+            continue;
+        };
+        for behavior in &bullet.behaviors {
+            if let WeaponBehavior::Ignite {
+                width,
+                turns,
+                damage,
+            } = behavior
+            {
+                let fire = BurningGround {
+                    left: (impact.position.x - width / 2.0).max(terrain.left),
+                    right: (impact.position.x + width / 2.0).min(terrain.right()),
+                    turns_left: *turns,
+                    damage: *damage,
+                    owner: impact.owner,
+                };
+                commands.spawn((
+                    MaterialMesh2dBundle {
+                        mesh: Mesh2dHandle(meshes.add(fire.build_mesh(&terrain))),
+                        material: materials.add(Color::WHITE),
+                        // between terrain and tanks
+                        transform: Transform::from_translation(Vec3::new(0.0, 0.0, 0.5)),
+                        ..default()
+                    },
+                    fire,
+                ));
+            }
+        }
+    }
+}
+
+/// Damages the tank whose turn just started if it stands in fire and lets every fire burn down
+/// by one turn.
+pub fn burn_tanks(
+    mut commands: Commands,
+    state: Res<GameState>,
+    mut reader: EventReader<EndTurnEvent>,
+    mut fires: Query<(Entity, &mut BurningGround)>,
+    mut tanks: Query<(Entity, &mut Player, &Transform), With<Tank>>,
+    mut kill_writer: EventWriter<PlayerKillEvent>,
+) {
+    for _ in reader.read() {
+        if state.mode != GameMode::Battle {
+            continue;
+        }
+        for (entity, mut player, transform) in &mut tanks {
+            if player.player_number != state.active_player {
+                continue;
+            }
+            let x = transform.translation.x;
+            let damage: i32 = fires
+                .iter()
+                .filter(|(_, fire)| fire.contains(x))
+                .map(|(_, fire)| fire.damage)
+                .sum();
+            if damage == 0 {
+                continue;
+            }
+            let killer = fires
+                .iter()
+                .find(|(_, fire)| fire.contains(x))
+                .map_or(state.active_player, |(_, fire)| fire.owner);
+            damage_tank(
+                &mut commands,
+                &mut kill_writer,
+                entity,
+                &mut player,
+                damage,
+                killer,
+            );
+        }
+        for (entity, mut fire) in &mut fires {
+            fire.turns_left = fire.turns_left.saturating_sub(1);
+            if fire.turns_left == 0 {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+/// Keeps the flames on the surface after the terrain was carved or settled.
+pub fn rebuild_fire_meshes(
+    terrain: Res<Terrain>,
+    fires: Query<(&BurningGround, &Mesh2dHandle)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !terrain.is_changed() {
+        return;
+    }
+    for (fire, handle) in &fires {
+        if let Some(mesh) = meshes.get_mut(&handle.0) {
+            *mesh = fire.build_mesh(&terrain);
+        }
+    }
+}
+
+/// Puts out every fire when a new round starts.
+pub fn extinguish_fires(
+    mut commands: Commands,
+    mut reader: EventReader<ResetEvent>,
+    fires: Query<Entity, With<BurningGround>>,
+) {
+    if reader.read().next().is_none() {
+        return;
+    }
+    for entity in &fires {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,218 @@
+use std::{f32::consts::PI, fmt::Display};
+
+use enum_iterator::Sequence;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    liquid::{Liquid, LiquidLayer},
+    material::BEDROCK_DEPTH,
+};
+
+/// Named generator configurations selectable in the start menu.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Sequence)]
+pub enum TerrainPreset {
+    RollingHills,
+    Mountains,
+    Canyon,
+    FlatPlain,
+    Islands,
+}
+
+impl Display for TerrainPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let preset_str = match self {
+            TerrainPreset::RollingHills => "Rolling Hills",
+            TerrainPreset::Mountains => "Mountains",
+            TerrainPreset::Canyon => "Canyon",
+            TerrainPreset::FlatPlain => "Flat Plain",
+            TerrainPreset::Islands => "Islands",
+        };
+        f.write_str(preset_str)
+    }
+}
+
+impl TerrainPreset {
+    pub fn params(&self) -> GeneratorParams {
+        match self {
+            TerrainPreset::RollingHills => GeneratorParams::default(),
+            TerrainPreset::Mountains => GeneratorParams {
+                base_height: 350.0,
+                amplitude: 900.0,
+                roughness: 0.6,
+                max_height: 900.0,
+                ..GeneratorParams::default()
+            },
+            TerrainPreset::Canyon => GeneratorParams {
+                base_height: 450.0,
+                amplitude: 150.0,
+                shape: TerrainShape::Canyon {
+                    width: 0.2,
+                    depth: 400.0,
+                },
+                sand_depth: 20.0,
+                liquid: Some(LiquidLayer {
+                    kind: Liquid::Lava,
+                    level: 100.0,
+                }),
+                ..GeneratorParams::default()
+            },
+            TerrainPreset::FlatPlain => GeneratorParams {
+                base_height: 150.0,
+                amplitude: 20.0,
+                roughness: 0.4,
+                sand_depth: 10.0,
+                ..GeneratorParams::default()
+            },
+            TerrainPreset::Islands => GeneratorParams {
+                base_height: 120.0,
+                amplitude: 500.0,
+                roughness: 0.5,
+                shape: TerrainShape::Islands { sea_level: 100.0 },
+                sand_depth: 30.0,
+                liquid: Some(LiquidLayer {
+                    kind: Liquid::Water,
+                    level: 100.0,
+                }),
+                ..GeneratorParams::default()
+            },
+        }
+    }
+}
+
+/// Post processing applied to the generated heights.
+#[derive(Clone, Copy)]
+pub enum TerrainShape {
+    Open,
+    /// Cuts a valley into the middle of the map, `width` is a fraction of the map width.
+    Canyon {
+        width: f32,
+        depth: f32,
+    },
+    /// Removes every column below `sea_level`, leaving gaps between islands.
+    Islands {
+        sea_level: f32,
+    },
+}
+
+/// Parameters for the midpoint displacement terrain generator.
+#[derive(Clone, Copy)]
+pub struct GeneratorParams {
+    /// Average height of the terrain.
+    pub base_height: f32,
+    /// Maximum displacement of the first subdivision.
+    pub amplitude: f32,
+    /// Factor the displacement is multiplied with on every subdivision, lower is smoother.
+    pub roughness: f32,
+    pub min_height: f32,
+    pub max_height: f32,
+    /// Number of box blur passes applied after generation.
+    pub smoothing: u32,
+    pub shape: TerrainShape,
+    /// Thickness of the indestructible layer at the bottom.
+    pub bedrock_depth: f32,
+    /// Thickness of the sand layer on top.
+    pub sand_depth: f32,
+    /// Liquid filling the low-lying parts of the map.
+    pub liquid: Option<LiquidLayer>,
+}
+
+impl Default for GeneratorParams {
+    fn default() -> Self {
+        GeneratorParams {
+            base_height: 200.0,
+            amplitude: 250.0,
+            roughness: 0.55,
+            min_height: 20.0,
+            max_height: 550.0,
+            smoothing: 3,
+            shape: TerrainShape::Open,
+            bedrock_depth: BEDROCK_DEPTH,
+            sand_depth: 0.0,
+            liquid: None,
+        }
+    }
+}
+
+/// Generates `columns` heights using midpoint displacement.
+/// The same seed and params always produce the same heights.
+pub fn generate_heights(seed: u64, params: &GeneratorParams, columns: usize) -> Vec<f32> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let size = columns.saturating_sub(1).next_power_of_two() + 1;
+    let mut heights = vec![params.base_height; size];
+    heights[0] += rng.gen_range(-1.0..=1.0) * params.amplitude / 2.0;
+    heights[size - 1] += rng.gen_range(-1.0..=1.0) * params.amplitude / 2.0;
+
+    let mut step = size - 1;
+    let mut displacement = params.amplitude;
+    while step > 1 {
+        let half = step / 2;
+        for start in (0..size - 1).step_by(step) {
+            let midpoint = (heights[start] + heights[start + step]) / 2.0;
+            heights[start + half] = midpoint + rng.gen_range(-1.0..=1.0) * displacement;
+        }
+        displacement *= params.roughness;
+        step = half;
+    }
+
+    heights.truncate(columns);
+    for _ in 0..params.smoothing {
+        heights = smooth(&heights);
+    }
+    if let TerrainShape::Canyon { width, depth } = params.shape {
+        for (index, height) in heights.iter_mut().enumerate() {
+            let distance = (index as f32 / columns as f32 - 0.5) / width;
+            if distance.abs() < 1.0 {
+                *height -= depth * ((distance * PI).cos() + 1.0) / 2.0;
+            }
+        }
+    }
+    heights
+        .iter()
+        .map(|height| {
+            let height = height.clamp(params.min_height, params.max_height);
+            match params.shape {
+                TerrainShape::Islands { sea_level } if height < sea_level => 0.0,
+                _ => height,
+            }
+        })
+        .collect()
+}
+
+fn smooth(heights: &[f32]) -> Vec<f32> {
+    let last = heights.len().saturating_sub(1);
+    (0..heights.len())
+        .map(|index| {
+            let previous = heights[index.saturating_sub(1)];
+            let next = heights[(index + 1).min(last)];
+            (previous + heights[index] + next) / 3.0
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLUMNS: usize = 3841;
+
+    #[test]
+    fn same_seed_reproduces_heights() {
+        for preset in enum_iterator::all::<TerrainPreset>() {
+            let params = preset.params();
+            assert_eq!(
+                generate_heights(42, &params, COLUMNS),
+                generate_heights(42, &params, COLUMNS),
+                "{preset} differs for the same seed"
+            );
+        }
+    }
+
+    #[test]
+    fn different_seeds_produce_different_heights() {
+        let params = TerrainPreset::RollingHills.params();
+        assert_ne!(
+            generate_heights(1, &params, COLUMNS),
+            generate_heights(2, &params, COLUMNS)
+        );
+    }
+}
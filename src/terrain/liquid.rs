@@ -0,0 +1,144 @@
+use bevy::{
+    color::{Color, ColorToComponents, LinearRgba},
+    math::Vec2,
+    prelude::{
+        Commands, Component, DespawnRecursiveExt, Entity, EventReader, EventWriter, Query, Res,
+        Transform,
+    },
+};
+use serde::Deserialize;
+
+use crate::{
+    bullets::Bullet,
+    tank::{Tank, TANK_GROUND_OFFSET},
+    utils::{
+        damage_tank, EndTurnEvent, GameMode, GameState, Player, PlayerKillEvent,
+        ProjectileDespawned,
+    },
+    weapons::WeaponId,
+};
+
+use super::Terrain;
+
+/// Factor projectile velocity is multiplied with every fixed step spent in water.
+const WATER_DRAG: f32 = 0.85;
+/// Projectiles slower than this in world units per second are extinguished by water.
+const EXTINGUISH_SPEED: f32 = 15.0;
+/// Damage dealt at the end of every turn to tanks standing in lava.
+pub const LAVA_DAMAGE: i32 = 15;
+/// Tanks whose bottom lies deeper than this below the water surface drown.
+pub const DROWNING_DEPTH: f32 = 60.0;
+
+#[derive(Component)]
+pub struct LiquidSurface {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+pub enum Liquid {
+    /// Slows and extinguishes projectiles, drowns tanks.
+    Water,
+    /// Damages tanks standing in it every turn.
+    Lava,
+}
+
+impl Liquid {
+    pub fn color(&self) -> Color {
+        match self {
+            Liquid::Water => Color::srgba(0.15, 0.35, 0.85, 0.7),
+            Liquid::Lava => Color::srgba(1.0, 0.35, 0.0, 0.9),
+        }
+    }
+
+    pub fn vertex_color(&self) -> [f32; 4] {
+        LinearRgba::from(self.color()).to_f32_array()
+    }
+}
+
+/// Liquid filling every column whose ground lies below `level` when the map is built.
+#[derive(Clone, Copy, Deserialize)]
+pub struct LiquidLayer {
+    pub kind: Liquid,
+    /// Height of the liquid surface relative to the terrain base.
+    pub level: f32,
+}
+
+pub fn bullets_in_liquid(
+    mut commands: Commands,
+    terrain: Res<Terrain>,
+    mut bullets: Query<(Entity, &mut Bullet, &WeaponId, &Transform)>,
+    mut despawn_writer: EventWriter<ProjectileDespawned>,
+) {
+    for (entity, mut bullet, weapon, transform) in &mut bullets {
+        if terrain.liquid_at(transform.translation.truncate()) != Some(Liquid::Water) {
+            continue;
+        }
+        bullet.velocity_shot *= WATER_DRAG;
+        if bullet.velocity_shot.length() < EXTINGUISH_SPEED {
+            despawn_writer.send(ProjectileDespawned {
+                entity,
+                weapon: weapon.clone(),
+                owner: bullet.owner,
+                position: transform.translation.truncate(),
+            });
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Damages every tank standing in lava at the end of a battle turn.
+pub fn lava_damage(
+    mut commands: Commands,
+    terrain: Res<Terrain>,
+    state: Res<GameState>,
+    mut reader: EventReader<EndTurnEvent>,
+    mut tanks: Query<(Entity, &mut Player, &Tank, &Transform)>,
+    mut kill_writer: EventWriter<PlayerKillEvent>,
+) {
+    for _ in reader.read() {
+        if state.mode != GameMode::Battle {
+            continue;
+        }
+        for (entity, mut player, tank, transform) in &mut tanks {
+            if !matches!(
+                terrain.liquid_depth_at(transform.translation.x),
+                Some((Liquid::Lava, _))
+            ) {
+                continue;
+            }
+            let killer = tank.undermined_by.unwrap_or(player.player_number);
+            damage_tank(
+                &mut commands,
+                &mut kill_writer,
+                entity,
+                &mut player,
+                LAVA_DAMAGE,
+                killer,
+            );
+        }
+    }
+}
+
+/// Destroys tanks sinking deeper than `DROWNING_DEPTH` into water.
+pub fn drown_tanks(
+    mut commands: Commands,
+    terrain: Res<Terrain>,
+    mut tanks: Query<(Entity, &mut Player, &Tank, &Transform)>,
+    mut kill_writer: EventWriter<PlayerKillEvent>,
+) {
+    for (entity, mut player, tank, transform) in &mut tanks {
+        let bottom = transform.translation.truncate() - Vec2::Y * TANK_GROUND_OFFSET;
+        if let Some((Liquid::Water, depth)) = terrain.depth_below_surface(bottom) {
+            if depth > DROWNING_DEPTH {
+                let killer = tank.undermined_by.unwrap_or(player.player_number);
+                let health = player.health.max(0);
+                damage_tank(
+                    &mut commands,
+                    &mut kill_writer,
+                    entity,
+                    &mut player,
+                    health + 1,
+                    killer,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt::Display;
+
+use bevy::{
+    asset::{
+        io::Reader, Asset, AssetEvent, AssetLoader, AssetServer, Assets, AsyncReadExt, Handle,
+        LoadContext, LoadState, LoadedFolder,
+    },
+    prelude::{EventReader, Image, Res, ResMut, Resource},
+    reflect::TypePath,
+    render::{
+        render_asset::RenderAssetUsages,
+        texture::{CompressedImageFormats, ImageSampler, ImageType},
+    },
+};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{tank::TANK_GRAVITY, world::WorldBounds};
+
+use super::liquid::LiquidLayer;
+
+/// World height the full height of a `*.map.png` file is scaled to.
+const IMAGE_MAP_HEIGHT: f32 = 1000.0;
+/// Projectile gravity of maps without own rules, tanks fall with `TANK_GRAVITY` at this value.
+const DEFAULT_GRAVITY: f32 = 300.0;
+
+/// Designer authored map, loaded from `*.map.ron` or `*.map.png` files in `assets/maps`.
+#[derive(Asset, TypePath, Deserialize)]
+pub struct MapAsset {
+    /// Terrain heights, stretched across the whole width of the world.
+    pub heights: Vec<f32>,
+    #[serde(default)]
+    pub rules: MapRules,
+    #[serde(default)]
+    pub liquid: Option<LiquidLayer>,
+}
+
+/// Match rules of the currently played map.
+#[derive(Resource, Clone, Deserialize)]
+#[serde(default)]
+pub struct MapRules {
+    /// World x positions tanks spawn at, in player order.
+    pub spawn_points: Vec<f32>,
+    /// Minimum and maximum wind, horizontal acceleration in world units per second squared.
+    /// Negative wind blows to the left.
+    pub wind: (f32, f32),
+    /// Downward acceleration of projectiles in world units per second squared. Tanks fall faster
+    /// by the same factor as on maps with the default gravity.
+    pub gravity: f32,
+}
+
+impl Default for MapRules {
+    fn default() -> Self {
+        MapRules {
+            spawn_points: Vec::new(),
+            wind: (-60.0, 60.0),
+            gravity: DEFAULT_GRAVITY,
+        }
+    }
+}
+
+impl MapRules {
+    /// Returns the spawn x coordinate of the player, spreading players without a configured
+    /// spawn point evenly across the world.
+    pub fn spawn_point(&self, index: u32, player_count: u32, bounds: &WorldBounds) -> f32 {
+        self.spawn_points
+            .get(index as usize)
+            .copied()
+            .unwrap_or_else(|| bounds.spread_x(index, player_count))
+    }
+
+    /// Downward acceleration of falling tanks in world units per second squared.
+    pub fn tank_gravity(&self) -> f32 {
+        TANK_GRAVITY * self.gravity / DEFAULT_GRAVITY
+    }
+
+    pub fn random_wind(&self) -> f32 {
+        let (min, max) = self.wind;
+        if min < max {
+            rand::thread_rng().gen_range(min..=max)
+        } else {
+            min
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MapLoadError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Image(String),
+    NoHeights,
+}
+
+impl Display for MapLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapLoadError::Io(error) => write!(f, "Could not read map: {}", error),
+            MapLoadError::Ron(error) => write!(f, "Could not parse map: {}", error),
+            MapLoadError::Image(error) => write!(f, "Could not decode map image: {}", error),
+            MapLoadError::NoHeights => f.write_str("Map does not contain any terrain"),
+        }
+    }
+}
+
+impl std::error::Error for MapLoadError {}
+
+impl From<std::io::Error> for MapLoadError {
+    fn from(value: std::io::Error) -> Self {
+        MapLoadError::Io(value)
+    }
+}
+
+impl From<ron::error::SpannedError> for MapLoadError {
+    fn from(value: ron::error::SpannedError) -> Self {
+        MapLoadError::Ron(value)
+    }
+}
+
+#[derive(Default)]
+pub struct RonMapLoader;
+
+impl AssetLoader for RonMapLoader {
+    type Asset = MapAsset;
+    type Settings = ();
+    type Error = MapLoadError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _: &'a (),
+        _: &'a mut LoadContext<'_>,
+    ) -> Result<MapAsset, MapLoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let map = ron::de::from_bytes::<MapAsset>(&bytes)?;
+        if map.heights.is_empty() {
+            return Err(MapLoadError::NoHeights);
+        }
+        Ok(map)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.ron"]
+    }
+}
+
+/// Loads a grayscale silhouette, bright pixels are solid ground.
+#[derive(Default)]
+pub struct ImageMapLoader;
+
+impl AssetLoader for ImageMapLoader {
+    type Asset = MapAsset;
+    type Settings = ();
+    type Error = MapLoadError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _: &'a (),
+        _: &'a mut LoadContext<'_>,
+    ) -> Result<MapAsset, MapLoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let image = Image::from_buffer(
+            &bytes,
+            ImageType::Extension("png"),
+            CompressedImageFormats::NONE,
+            false,
+            ImageSampler::Default,
+            RenderAssetUsages::MAIN_WORLD,
+        )
+        .map_err(|error| MapLoadError::Image(error.to_string()))?
+        .try_into_dynamic()
+        .map_err(|error| MapLoadError::Image(error.to_string()))?
+        .to_luma8();
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Err(MapLoadError::NoHeights);
+        }
+        let heights = (0..width)
+            .map(|x| {
+                let top = (0..height).find(|y| image.get_pixel(x, *y).0[0] > 127);
+                let solid_pixels = top.map_or(0, |top| height - top);
+                solid_pixels as f32 / height as f32 * IMAGE_MAP_HEIGHT
+            })
+            .collect();
+        Ok(MapAsset {
+            heights,
+            rules: MapRules::default(),
+            liquid: None,
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map.png"]
+    }
+}
+
+/// Where the terrain of the next round comes from.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum MapSource {
+    Generated,
+    File(String),
+}
+
+impl Display for MapSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapSource::Generated => f.write_str("Generated"),
+            MapSource::File(name) => f.write_str(name),
+        }
+    }
+}
+
+/// Why the selected map file can't be played.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MapNotLoaded {
+    /// The file has not finished loading yet.
+    Loading,
+    /// The file could not be loaded or parsed, another map has to be picked.
+    Failed,
+}
+
+/// All map files found in `assets/maps`.
+#[derive(Resource)]
+pub struct MapLibrary {
+    folder: Handle<LoadedFolder>,
+    maps: Vec<(String, Handle<MapAsset>)>,
+}
+
+impl MapLibrary {
+    pub fn new(folder: Handle<LoadedFolder>) -> Self {
+        MapLibrary {
+            folder,
+            maps: Vec::new(),
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.maps.iter().map(|(name, _)| name)
+    }
+
+    /// Returns the selected map, `None` for generated terrain.
+    /// Fails while the map file is not loaded and once loading it failed.
+    pub fn selected<'a>(
+        &self,
+        source: &MapSource,
+        maps: &'a Assets<MapAsset>,
+        asset_server: &AssetServer,
+    ) -> Result<Option<&'a MapAsset>, MapNotLoaded> {
+        let name = match source {
+            MapSource::Generated => return Ok(None),
+            MapSource::File(name) => name,
+        };
+        let handle = self.get(name).ok_or(MapNotLoaded::Loading)?;
+        if let Some(map) = maps.get(handle) {
+            Ok(Some(map))
+        } else if let Some(LoadState::Failed(_)) = asset_server.get_load_state(handle) {
+            Err(MapNotLoaded::Failed)
+        } else {
+            Err(MapNotLoaded::Loading)
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Handle<MapAsset>> {
+        self.maps
+            .iter()
+            .find(|(map_name, _)| map_name == name)
+            .map(|(_, handle)| handle)
+    }
+}
+
+pub fn collect_maps(
+    mut library: ResMut<MapLibrary>,
+    mut events: EventReader<AssetEvent<LoadedFolder>>,
+    folders: Res<Assets<LoadedFolder>>,
+) {
+    for event in events.read() {
+        // files failing to load keep the folder from ever loading with dependencies, they are
+        // listed anyway so picking one reports the error
+        if !event.is_added(&library.folder) && !event.is_loaded_with_dependencies(&library.folder) {
+            continue;
+        }
+        if let Some(folder) = folders.get(&library.folder) {
+            library.maps = folder
+                .handles
+                .iter()
+                .filter_map(|handle| {
+                    let name = handle.path()?.path().file_name()?.to_str()?.to_string();
+                    Some((name, handle.clone().try_typed::<MapAsset>().ok()?))
+                })
+                .collect();
+        }
+    }
+}
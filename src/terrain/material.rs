@@ -0,0 +1,37 @@
+use bevy::color::{Color, ColorToComponents, LinearRgba};
+
+/// Default thickness of the indestructible layer at the bottom of the map.
+pub const BEDROCK_DEPTH: f32 = 30.0;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TerrainMaterial {
+    /// Packed harder than sand, explosions leave smaller craters.
+    Dirt,
+    /// Slides down slopes steeper than its angle of repose, the softest ground.
+    Sand,
+    /// Cannot be destroyed by explosions.
+    Bedrock,
+}
+
+impl TerrainMaterial {
+    /// Factor the crater radius is reduced by, 1.0 is indestructible.
+    pub fn hardness(&self) -> f32 {
+        match self {
+            TerrainMaterial::Dirt => 0.2,
+            TerrainMaterial::Sand => 0.0,
+            TerrainMaterial::Bedrock => 1.0,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            TerrainMaterial::Dirt => Color::srgb(0.35, 0.24, 0.14),
+            TerrainMaterial::Sand => Color::srgb(0.86, 0.76, 0.48),
+            TerrainMaterial::Bedrock => Color::srgb(0.25, 0.25, 0.28),
+        }
+    }
+
+    pub fn vertex_color(&self) -> [f32; 4] {
+        LinearRgba::from(self.color()).to_f32_array()
+    }
+}
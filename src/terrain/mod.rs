@@ -0,0 +1,627 @@
+use bevy::{
+    asset::{AssetServer, Assets},
+    log::error,
+    math::Vec2,
+    prelude::{Component, DetectChanges, EventReader, Mesh, Query, Res, ResMut, Resource, With},
+    render::{mesh::PrimitiveTopology, render_asset::RenderAssetUsages},
+    sprite::Mesh2dHandle,
+    time::Time,
+};
+use generator::{generate_heights, GeneratorParams};
+use liquid::{Liquid, LiquidLayer, LiquidSurface};
+use map::{MapAsset, MapLibrary, MapRules};
+use material::{TerrainMaterial, BEDROCK_DEPTH};
+
+use crate::utils::{GameState, ResetEvent};
+
+pub mod fire;
+pub mod generator;
+pub mod liquid;
+pub mod map;
+pub mod material;
+
+/// Horizontal distance between two height samples in world units.
+pub const COLUMN_WIDTH: f32 = 1.0;
+/// Speed in world units per second floating dirt falls with.
+const SETTLE_SPEED: f32 = 400.0;
+/// Leftover pieces of dirt smaller than this are removed.
+const MIN_SPAN_HEIGHT: f32 = 0.5;
+/// Largest height difference between neighbouring columns sand rests at.
+const SAND_REPOSE_HEIGHT: f32 = 0.8;
+/// Sand stops sliding once less than this would move, so settling ends.
+const MIN_SLIDE_AMOUNT: f32 = 0.5;
+
+#[derive(Component)]
+pub struct Wall {}
+
+/// Vertical run of solid ground inside a column, relative to the terrain base.
+#[derive(Clone, Copy)]
+struct Span {
+    bottom: f32,
+    top: f32,
+    material: TerrainMaterial,
+}
+
+impl Span {
+    fn height(&self) -> f32 {
+        self.top - self.bottom
+    }
+}
+
+/// Terrain backing the `Wall` mesh.
+/// Every column starting at `left` holds its solid spans ordered from bottom to top, relative
+/// to `base`. Carving can leave floating spans behind which fall down in `settle_terrain`.
+/// Columns lying below the liquid level when the map is built are flooded, liquid flows into
+/// neighbouring columns carved below the level.
+#[derive(Resource, Clone)]
+pub struct Terrain {
+    pub left: f32,
+    pub base: f32,
+    columns: Vec<Vec<Span>>,
+    liquid: Option<LiquidLayer>,
+    flooded: Vec<bool>,
+    settling: bool,
+}
+
+/// Builds a column of bedrock, dirt and a top layer of `sand_depth` sand.
+fn column_from_height(height: f32, bedrock_depth: f32, sand_depth: f32) -> Vec<Span> {
+    let bedrock_top = bedrock_depth.min(height);
+    let sand_bottom = (height - sand_depth).max(bedrock_top);
+    [
+        Span {
+            bottom: 0.0,
+            top: bedrock_top,
+            material: TerrainMaterial::Bedrock,
+        },
+        Span {
+            bottom: bedrock_top,
+            top: sand_bottom,
+            material: TerrainMaterial::Dirt,
+        },
+        Span {
+            bottom: sand_bottom,
+            top: height,
+            material: TerrainMaterial::Sand,
+        },
+    ]
+    .into_iter()
+    .filter(|span| span.height() > 0.0)
+    .collect()
+}
+
+/// Removes up to `amount` from the top of the column if it is sand, returns the removed amount.
+fn take_sand(column: &mut Vec<Span>, amount: f32) -> f32 {
+    match column.last_mut() {
+        Some(span) if span.material == TerrainMaterial::Sand => {
+            let taken = amount.min(span.height());
+            span.top -= taken;
+            if span.height() <= 0.0 {
+                column.pop();
+            }
+            taken
+        }
+        _ => 0.0,
+    }
+}
+
+fn add_sand(column: &mut Vec<Span>, amount: f32) {
+    match column.last_mut() {
+        Some(span) if span.material == TerrainMaterial::Sand => span.top += amount,
+        last => {
+            let top = last.map_or(0.0, |span| span.top);
+            column.push(Span {
+                bottom: top,
+                top: top + amount,
+                material: TerrainMaterial::Sand,
+            });
+        }
+    }
+}
+
+/// Returns the first parameter in `start..=end` at which `y + rise * t` lies within
+/// `bottom..=top`.
+fn interval_entry(y: f32, rise: f32, start: f32, end: f32, bottom: f32, top: f32) -> Option<f32> {
+    if rise == 0.0 {
+        return (bottom <= y && y <= top).then_some(start);
+    }
+    let first = (bottom - y) / rise;
+    let second = (top - y) / rise;
+    let enter = first.min(second).max(start);
+    (enter <= first.max(second).min(end)).then_some(enter)
+}
+
+impl Terrain {
+    /// Creates the columns between `left` and `right` (inclusive) without any ground, the
+    /// heights are filled in by `regenerate` or `load_heights`.
+    pub fn empty(left: i32, right: i32, base: f32) -> Terrain {
+        let columns = ((right - left) as f32 / COLUMN_WIDTH) as usize + 1;
+        Terrain {
+            left: left as f32,
+            base,
+            columns: vec![Vec::new(); columns],
+            liquid: None,
+            flooded: Vec::new(),
+            settling: false,
+        }
+    }
+
+    fn set_heights(
+        &mut self,
+        heights: impl Iterator<Item = f32>,
+        bedrock: f32,
+        sand: f32,
+        liquid: Option<LiquidLayer>,
+    ) {
+        let heights: Vec<f32> = heights.collect();
+        let last = heights.len() - 1;
+        self.columns = (0..heights.len())
+            .map(|index| {
+                // sand only covers slopes it can rest on
+                let rise = heights[(index + 1).min(last)] - heights[index.saturating_sub(1)];
+                let sand = if rise.abs() / 2.0 <= SAND_REPOSE_HEIGHT {
+                    sand
+                } else {
+                    0.0
+                };
+                column_from_height(heights[index], bedrock, sand)
+            })
+            .collect();
+        self.flooded = heights
+            .iter()
+            .map(|height| liquid.is_some_and(|layer| *height < layer.level))
+            .collect();
+        self.liquid = liquid;
+        self.settling = false;
+    }
+
+    /// Replaces the terrain with a new map of the same size.
+    pub fn regenerate(&mut self, seed: u64, params: &GeneratorParams) {
+        let heights = generate_heights(seed, params, self.columns.len());
+        self.set_heights(
+            heights.into_iter(),
+            params.bedrock_depth,
+            params.sand_depth,
+            params.liquid,
+        );
+    }
+
+    /// Replaces the terrain by stretching `heights` across the whole map.
+    pub fn load_heights(&mut self, heights: &[f32], liquid: Option<LiquidLayer>) {
+        let columns = self.columns.len();
+        let last = heights.len() - 1;
+        self.set_heights(
+            (0..columns).map(|index| {
+                let position = index as f32 / (columns - 1).max(1) as f32 * last as f32;
+                let lower = position.floor() as usize;
+                let upper = (lower + 1).min(last);
+                let fraction = position - lower as f32;
+                heights[lower] * (1.0 - fraction) + heights[upper] * fraction
+            }),
+            BEDROCK_DEPTH,
+            0.0,
+            liquid,
+        );
+    }
+
+    pub fn right(&self) -> f32 {
+        self.left + (self.columns.len() - 1) as f32 * COLUMN_WIDTH
+    }
+
+    fn column_index(&self, x: f32) -> Option<usize> {
+        let offset = ((x - self.left) / COLUMN_WIDTH).floor();
+        if offset >= 0.0 && (offset as usize) < self.columns.len() {
+            Some(offset as usize)
+        } else {
+            None
+        }
+    }
+
+    fn column_top(&self, index: usize) -> f32 {
+        self.columns[index].last().map_or(0.0, |span| span.top)
+    }
+
+    /// Returns the world y coordinate of the highest surface at the world x coordinate.
+    /// Positions outside of the map use the height of the closest edge.
+    pub fn height_at(&self, x: f32) -> f32 {
+        let last = self.columns.len() - 1;
+        let offset = ((x - self.left) / COLUMN_WIDTH).clamp(0.0, last as f32);
+        let index = offset.floor() as usize;
+        let next = (index + 1).min(last);
+        let fraction = offset - index as f32;
+        self.base + self.column_top(index) * (1.0 - fraction) + self.column_top(next) * fraction
+    }
+
+    /// Returns the angle of the surface in radians around `x`, averaged over `width`.
+    pub fn slope_angle_at(&self, x: f32, width: f32) -> f32 {
+        let half_width = width / 2.0;
+        (self.height_at(x + half_width) - self.height_at(x - half_width)).atan2(width)
+    }
+
+    /// Returns the unit vector along the surface around `x` pointing to the right, averaged over
+    /// `width`.
+    pub fn tangent_at(&self, x: f32, width: f32) -> Vec2 {
+        Vec2::from_angle(self.slope_angle_at(x, width))
+    }
+
+    /// Returns the unit normal of the surface at `x`, pointing out of the ground.
+    pub fn normal_at(&self, x: f32) -> Vec2 {
+        self.tangent_at(x, 2.0 * COLUMN_WIDTH).perp()
+    }
+
+    /// Returns the first point along the segment from `from` to `to` inside solid ground.
+    /// Every crossed column is tested exactly, so fast projectiles can't pass through thin walls.
+    pub fn raycast(&self, from: Vec2, to: Vec2) -> Option<Vec2> {
+        let delta = to - from;
+        let mut crossings = vec![0.0, 1.0];
+        if delta.x != 0.0 {
+            let first = ((from.x.min(to.x) - self.left) / COLUMN_WIDTH).ceil() as i64;
+            let last = ((from.x.max(to.x) - self.left) / COLUMN_WIDTH).floor() as i64;
+            crossings.extend(
+                (first..=last)
+                    .map(|edge| (self.left + edge as f32 * COLUMN_WIDTH - from.x) / delta.x),
+            );
+            crossings.sort_by(f32::total_cmp);
+        }
+        let y = from.y - self.base;
+        crossings
+            .windows(2)
+            .find_map(|window| {
+                let (start, end) = (window[0], window[1]);
+                let spans = self
+                    .column_index(from.x + delta.x * (start + end) / 2.0)
+                    .map_or(&[][..], |index| &self.columns[index][..]);
+                // everything below the base is solid
+                std::iter::once((f32::NEG_INFINITY, 0.0))
+                    .chain(spans.iter().map(|span| (span.bottom, span.top)))
+                    .filter_map(|(bottom, top)| interval_entry(y, delta.y, start, end, bottom, top))
+                    .min_by(f32::total_cmp)
+            })
+            .map(|t| from + delta * t)
+    }
+
+    /// Whether the world position lies inside solid ground.
+    pub fn is_solid(&self, position: Vec2) -> bool {
+        let y = position.y - self.base;
+        if y < 0.0 {
+            return true;
+        }
+        self.column_index(position.x).is_some_and(|index| {
+            self.columns[index]
+                .iter()
+                .any(|span| span.bottom <= y && y <= span.top)
+        })
+    }
+
+    /// Returns the material of the ground at the world position, `None` in the air. Everything
+    /// below the base counts as bedrock.
+    pub fn material_at(&self, position: Vec2) -> Option<TerrainMaterial> {
+        let y = position.y - self.base;
+        if y < 0.0 {
+            return Some(TerrainMaterial::Bedrock);
+        }
+        self.column_index(position.x)
+            .and_then(|index| {
+                self.columns[index]
+                    .iter()
+                    .find(|span| span.bottom <= y && y <= span.top)
+            })
+            .map(|span| span.material)
+    }
+
+    /// Returns the hardness of the ground at the world position, 0 in the air.
+    pub fn hardness_at(&self, position: Vec2) -> f32 {
+        self.material_at(position)
+            .map_or(0.0, |material| material.hardness())
+    }
+
+    /// Returns the liquid at the world position if it lies in a flooded column below the surface
+    /// and outside of solid ground.
+    pub fn liquid_at(&self, position: Vec2) -> Option<Liquid> {
+        let layer = self.liquid?;
+        let index = self.column_index(position.x)?;
+        if self.flooded[index] && position.y - self.base < layer.level && !self.is_solid(position) {
+            Some(layer.kind)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the liquid covering the ground at the world x coordinate and how deep it is.
+    pub fn liquid_depth_at(&self, x: f32) -> Option<(Liquid, f32)> {
+        let layer = self.liquid?;
+        let index = self.column_index(x)?;
+        let depth = layer.level - self.column_top(index);
+        if self.flooded[index] && depth > 0.0 {
+            Some((layer.kind, depth))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the liquid above the world position and how far below its surface the position
+    /// lies.
+    pub fn depth_below_surface(&self, position: Vec2) -> Option<(Liquid, f32)> {
+        let layer = self.liquid?;
+        let index = self.column_index(position.x)?;
+        let depth = self.base + layer.level - position.y;
+        if self.flooded[index] && depth > 0.0 {
+            Some((layer.kind, depth))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the closest world x coordinate to `x` whose ground is not covered by liquid.
+    pub fn nearest_dry_x(&self, x: f32) -> f32 {
+        let is_dry = |index: usize| self.liquid_depth_at(self.column_x(index)).is_none();
+        match self.column_index(x) {
+            Some(start) => (0..self.columns.len())
+                .flat_map(|distance| [start.checked_sub(distance), Some(start + distance)])
+                .flatten()
+                .find(|index| *index < self.columns.len() && is_dry(*index))
+                .map_or(x, |index| self.column_x(index)),
+            None => x,
+        }
+    }
+
+    fn column_x(&self, index: usize) -> f32 {
+        self.left + index as f32 * COLUMN_WIDTH
+    }
+
+    /// Whether all dirt has come to rest.
+    pub fn is_settled(&self) -> bool {
+        !self.settling
+    }
+
+    /// Removes a circle of terrain around the world position `center`, shrunk by the hardness of
+    /// each material.
+    pub fn carve(&mut self, center: Vec2, radius: f32) {
+        let last = self.columns.len() - 1;
+        let first = ((center.x - radius - self.left) / COLUMN_WIDTH)
+            .floor()
+            .max(0.0) as usize;
+        let end = (((center.x + radius - self.left) / COLUMN_WIDTH).ceil() as usize).min(last);
+        for index in first..=end {
+            let dx = self.left + index as f32 * COLUMN_WIDTH - center.x;
+            if dx.abs() > radius {
+                continue;
+            }
+            let mut remaining = Vec::with_capacity(self.columns[index].len() + 1);
+            for span in self.columns[index].drain(..) {
+                let crater_radius = radius * (1.0 - span.material.hardness());
+                if dx.abs() >= crater_radius {
+                    remaining.push(span);
+                    continue;
+                }
+                let half_height = (crater_radius * crater_radius - dx * dx).sqrt();
+                let crater_bottom = center.y - half_height - self.base;
+                let crater_top = center.y + half_height - self.base;
+                if span.top <= crater_bottom || span.bottom >= crater_top {
+                    remaining.push(span);
+                    continue;
+                }
+                let below = Span {
+                    top: crater_bottom,
+                    ..span
+                };
+                let above = Span {
+                    bottom: crater_top,
+                    ..span
+                };
+                remaining.extend(
+                    [below, above]
+                        .into_iter()
+                        .filter(|part| part.height() > MIN_SPAN_HEIGHT),
+                );
+            }
+            self.columns[index] = remaining;
+        }
+        self.flood();
+        self.settling = true;
+    }
+
+    /// Carves a tunnel of `radius` along the segment from `from` to `to`.
+    pub fn carve_segment(&mut self, from: Vec2, to: Vec2, radius: f32) {
+        let steps = (from.distance(to) / (radius / 2.0)).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            self.carve(from.lerp(to, step as f32 / steps as f32), radius);
+        }
+    }
+
+    /// Returns the first point along the segment from `from` to `to`, sampled every `spacing`
+    /// units, that lies in ground too hard to carve: bedrock or anything below the base.
+    pub fn first_unbreakable(&self, from: Vec2, to: Vec2, spacing: f32) -> Option<Vec2> {
+        let steps = (from.distance(to) / spacing).ceil().max(1.0) as usize;
+        (0..=steps)
+            .map(|step| from.lerp(to, step as f32 / steps as f32))
+            .find(|point| self.hardness_at(*point) >= 1.0)
+    }
+
+    /// Lets liquid flow from flooded columns into neighbouring columns whose ground lies below
+    /// the liquid level, columns filled up above the level run dry.
+    fn flood(&mut self) {
+        let level = if let Some(layer) = self.liquid {
+            layer.level
+        } else {
+            return;
+        };
+        for index in 0..self.columns.len() {
+            self.flooded[index] &= self.column_top(index) < level;
+        }
+        for index in 1..self.columns.len() {
+            if self.flooded[index - 1] && self.column_top(index) < level {
+                self.flooded[index] = true;
+            }
+        }
+        for index in (0..self.columns.len() - 1).rev() {
+            if self.flooded[index + 1] && self.column_top(index) < level {
+                self.flooded[index] = true;
+            }
+        }
+    }
+
+    /// Lets every floating span fall by up to `distance`, merging spans of the same material
+    /// that land on each other, then lets sand slide towards lower neighbouring columns.
+    pub fn settle(&mut self, distance: f32) {
+        let mut moved = false;
+        for column in &mut self.columns {
+            let mut settled: Vec<Span> = Vec::with_capacity(column.len());
+            for span in column.drain(..) {
+                let floor = settled.last().map_or(0.0, |below| below.top);
+                let fall = (span.bottom - floor).min(distance).max(0.0);
+                moved |= fall > 0.0;
+                let span = Span {
+                    bottom: span.bottom - fall,
+                    top: span.top - fall,
+                    ..span
+                };
+                match settled.last_mut() {
+                    Some(below) if span.bottom <= below.top && below.material == span.material => {
+                        below.top = span.top
+                    }
+                    _ => settled.push(span),
+                }
+            }
+            *column = settled;
+        }
+        for index in 0..self.columns.len().saturating_sub(1) {
+            let difference = self.column_top(index) - self.column_top(index + 1);
+            let amount = ((difference.abs() - SAND_REPOSE_HEIGHT) / 2.0).min(distance);
+            if amount < MIN_SLIDE_AMOUNT {
+                continue;
+            }
+            let (left, right) = self.columns.split_at_mut(index + 1);
+            let (higher, lower) = if difference > 0.0 {
+                (&mut left[index], &mut right[0])
+            } else {
+                (&mut right[0], &mut left[index])
+            };
+            let slid = take_sand(higher, amount);
+            if slid > 0.0 {
+                add_sand(lower, slid);
+                moved = true;
+            }
+        }
+        self.settling = moved;
+        self.flood();
+    }
+
+    /// Builds the triangle mesh for the terrain relative to `base`, colored by material.
+    pub fn build_mesh(&self) -> Mesh {
+        let mut vertices = Vec::with_capacity(self.columns.len() * 6);
+        let mut colors = Vec::with_capacity(self.columns.len() * 6);
+        for (index, column) in self.columns.iter().enumerate() {
+            let x = self.left + index as f32 * COLUMN_WIDTH;
+            let next_x = x + COLUMN_WIDTH;
+            for span in column {
+                let top = [x, span.top, 0.0];
+                let next_bottom = [next_x, span.bottom, 0.0];
+                vertices.push([x, span.bottom, 0.0]);
+                vertices.push(top);
+                vertices.push(next_bottom);
+                vertices.push(next_bottom);
+                vertices.push(top);
+                vertices.push([next_x, span.top, 0.0]);
+                colors.extend([span.material.vertex_color(); 6]);
+            }
+        }
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    }
+
+    /// Builds the triangle mesh for the liquid relative to `base`, filling every flooded column
+    /// from its ground up to the liquid level.
+    pub fn build_liquid_mesh(&self) -> Mesh {
+        let mut vertices = Vec::new();
+        let mut colors = Vec::new();
+        if let Some(layer) = self.liquid {
+            for index in 0..self.columns.len() {
+                if !self.flooded[index] {
+                    continue;
+                }
+                let ground = self.column_top(index);
+                if ground >= layer.level {
+                    continue;
+                }
+                let x = self.column_x(index);
+                let next_x = x + COLUMN_WIDTH;
+                let top = [x, layer.level, 0.0];
+                let next_bottom = [next_x, ground, 0.0];
+                vertices.push([x, ground, 0.0]);
+                vertices.push(top);
+                vertices.push(next_bottom);
+                vertices.push(next_bottom);
+                vertices.push(top);
+                vertices.push([next_x, layer.level, 0.0]);
+                colors.extend([layer.kind.vertex_color(); 6]);
+            }
+        }
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::MAIN_WORLD | RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+        .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+    }
+}
+
+pub fn settle_terrain(time: Res<Time>, mut terrain: ResMut<Terrain>) {
+    if terrain.is_settled() {
+        return;
+    }
+    terrain.settle(SETTLE_SPEED * time.delta_seconds());
+}
+
+pub fn rebuild_terrain_mesh(
+    terrain: Res<Terrain>,
+    walls: Query<&Mesh2dHandle, With<Wall>>,
+    liquids: Query<&Mesh2dHandle, With<LiquidSurface>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    if !terrain.is_changed() {
+        return;
+    }
+    for handle in &walls {
+        if let Some(mesh) = meshes.get_mut(&handle.0) {
+            *mesh = terrain.build_mesh();
+        }
+    }
+    for handle in &liquids {
+        if let Some(mesh) = meshes.get_mut(&handle.0) {
+            *mesh = terrain.build_liquid_mesh();
+        }
+    }
+}
+
+pub fn reset_terrain(
+    mut state: ResMut<GameState>,
+    mut terrain: ResMut<Terrain>,
+    mut rules: ResMut<MapRules>,
+    library: Res<MapLibrary>,
+    maps: Res<Assets<MapAsset>>,
+    asset_server: Res<AssetServer>,
+    mut reader: EventReader<ResetEvent>,
+) {
+    if reader.read().next().is_none() {
+        return;
+    }
+    match library.selected(&state.map_source, &maps, &asset_server) {
+        Ok(Some(map)) => {
+            terrain.load_heights(&map.heights, map.liquid);
+            *rules = map.rules.clone();
+        }
+        Ok(None) => {
+            state.terrain_seed = rand::random();
+            terrain.regenerate(state.terrain_seed, &state.terrain_preset.params());
+            *rules = MapRules::default();
+        }
+        Err(error) => error!(
+            "Map {} can't be played ({error:?}), keeping the current terrain",
+            state.map_source
+        ),
+    }
+}
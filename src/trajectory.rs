@@ -0,0 +1,110 @@
+use std::fmt::Display;
+
+use bevy::{
+    color::Color,
+    prelude::{Gizmos, Query, Res, Transform},
+};
+use enum_iterator::Sequence;
+
+use crate::{
+    bullets::{ballistic_step, launch_velocity, BALLISTICS_RATE, MUZZLE_OFFSET},
+    tank::Tank,
+    terrain::{map::MapRules, Terrain},
+    utils::{GameMode, GameState, Player},
+    weapons::WeaponRegistry,
+    world::WorldBounds,
+};
+
+/// Default number of fixed steps shown by `TrajectoryPreview::Short`, see
+/// `GameState::short_preview_steps`.
+pub const DEFAULT_SHORT_PREVIEW_STEPS: usize = 20;
+/// Upper limit of simulated steps for the full arc.
+const MAX_PREVIEW_STEPS: usize = 1200;
+const PREVIEW_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.6);
+
+/// How much of the predicted flight path of the next shot is shown.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Sequence)]
+pub enum TrajectoryPreview {
+    Off,
+    /// Only the first few points after the muzzle.
+    Short,
+    /// The whole arc up to the impact.
+    Full,
+}
+
+impl Display for TrajectoryPreview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let preview_str = match self {
+            TrajectoryPreview::Off => "Off",
+            TrajectoryPreview::Short => "Short",
+            TrajectoryPreview::Full => "Full Arc",
+        };
+        f.write_str(preview_str)
+    }
+}
+
+/// Draws the predicted path of the active player's shot using the same steps as `move_bullets`.
+pub fn draw_trajectory(
+    mut gizmos: Gizmos,
+    state: Res<GameState>,
+    rules: Res<MapRules>,
+    terrain: Res<Terrain>,
+    bounds: Res<WorldBounds>,
+    weapons: Res<WeaponRegistry>,
+    tanks: Query<(&Player, &Tank, &Transform)>,
+) {
+    if state.mode != GameMode::Battle || state.firing {
+        return;
+    }
+    let steps = match state.trajectory_preview {
+        TrajectoryPreview::Off => return,
+        TrajectoryPreview::Short => state.short_preview_steps,
+        TrajectoryPreview::Full => MAX_PREVIEW_STEPS,
+    };
+    let active = tanks
+        .iter()
+        .find(|(player, _, _)| player.player_number == state.active_player);
+    let (player, tank, transform) = if let Some(active) = active {
+        active
+    } else {
+        return;
+    };
+    let exposure = if let Some(weapon) = weapons.get(&player.selected_weapon) {
+        weapon.exposure()
+    } else {
+        return;
+    };
+    let delta = (1.0 / BALLISTICS_RATE) as f32;
+    let mut position = transform.translation.truncate();
+    position.y += MUZZLE_OFFSET;
+    let mut velocity = launch_velocity(tank.shooting_direction.get(), player.fire_velocity);
+    let mut points = vec![position];
+    for _ in 0..steps {
+        let (next_position, next_velocity) = ballistic_step(
+            position,
+            velocity,
+            exposure,
+            state.wind,
+            rules.gravity,
+            delta,
+        );
+        if let Some(impact) = terrain.raycast(position, next_position) {
+            points.push(impact);
+            break;
+        }
+        position = next_position;
+        velocity = next_velocity;
+        points.push(position);
+        if position.x < bounds.left || position.x > bounds.right {
+            break;
+        }
+    }
+    match state.trajectory_preview {
+        TrajectoryPreview::Short => {
+            for point in points {
+                gizmos.circle_2d(point, 2.0, PREVIEW_COLOR);
+            }
+        }
+        _ => gizmos.linestrip_2d(points, PREVIEW_COLOR),
+    }
+}
@@ -3,7 +3,7 @@ use std::ops::RangeInclusive;
 
 use bevy::{
     asset::{AssetServer, Assets},
-    math::Vec2,
+    math::Vec3,
     prelude::{Commands, Entity, EventWriter, Mesh, Query, Res, ResMut, Transform},
     sprite::{ColorMaterial, Sprite},
     time::Time,
@@ -17,9 +17,11 @@ use bevy_iced::{
 };
 
 use crate::{
-    bullets::{BulletCount, BulletInfo, BulletType},
+    bullets::{launch_velocity, spawn_bullet, BulletCount, BulletInfo, MUZZLE_OFFSET},
     tank::Tank,
+    terrain::Terrain,
     utils::{get_current_player_props, GameMode, GameState, Player, ResetEvent},
+    weapons::{WeaponId, WeaponRegistry},
     UiMessage,
 };
 
@@ -31,7 +33,7 @@ pub enum BattleMessage {
     Fire,
     SetVelocity(f32),
     SetAngle(f32),
-    SelectBullet(BulletType),
+    SelectBullet(WeaponId),
     // UseRepair,
     // Teleport,
     // Parachute,
@@ -46,6 +48,7 @@ impl From<BattleMessage> for UiMessage {
 pub fn view_battle_ui(
     state: Res<GameState>,
     player_query: Query<(&Player, &Tank)>,
+    weapons: Res<WeaponRegistry>,
     mut ctx: IcedContext<UiMessage>,
 ) {
     let reset_button = button(text("Reset")).on_press(wrap(BattleMessage::Reset));
@@ -63,10 +66,10 @@ pub fn view_battle_ui(
             row![
                 shop_button,
                 reset_button,
-                bullet_picker(player).into(),
+                bullet_picker(player, &weapons).into(),
                 fuel(player).into(),
                 firing(player, tank).into(),
-                info_box(state.wind, player).into()
+                info_box(&state, player).into()
             ]
             .spacing(20)
             .padding(10)
@@ -88,6 +91,8 @@ pub fn update_battle_ui<'a>(
     mut state: ResMut<GameState>,
     mut reset_writer: EventWriter<ResetEvent>,
     asset_server: Res<AssetServer>,
+    terrain: Res<Terrain>,
+    weapons: Res<WeaponRegistry>,
 ) {
     let msgs: Vec<&BattleMessage> = messages
         .filter_map(|val| match val {
@@ -103,6 +108,11 @@ pub fn update_battle_ui<'a>(
         }
         return;
     }
+    let other_tanks: Vec<f32> = query
+        .iter()
+        .filter(|(_, player, _, _, _)| player.player_number != state.active_player)
+        .map(|(_, _, _, transform, _)| transform.translation.x)
+        .collect();
     let (_, mut player, mut tank, mut transform, _) =
         if let Some(props) = get_current_player_props(state.active_player, &mut query) {
             props
@@ -122,17 +132,32 @@ pub fn update_battle_ui<'a>(
                 reset_writer.send(ResetEvent {});
             }
             BattleMessage::MoveRight => {
-                transform.translation.x += player.drive(10) * delta;
+                let x = transform.translation.x;
+                let moved = player.drive(&tank, x, 1.0, delta, &terrain, &other_tanks);
+                if moved != 0.0 {
+                    tank.undermined_by = None;
+                }
+                transform.translation.x += moved;
             }
             BattleMessage::MoveLeft => {
-                transform.translation.x -= player.drive(10) * delta;
+                let x = transform.translation.x;
+                let moved = player.drive(&tank, x, -1.0, delta, &terrain, &other_tanks);
+                if moved != 0.0 {
+                    tank.undermined_by = None;
+                }
+                transform.translation.x += moved;
             }
             BattleMessage::Fire => {
+                let weapon = if let Some(weapon) = weapons.get(&player.selected_weapon) {
+                    weapon
+                } else {
+                    return;
+                };
                 state.firing = true;
-                let bullet_type = player.selected_bullet.0;
+                let weapon_id = weapon.id();
                 let count_type = *player
                     .inventory
-                    .get(&bullet_type)
+                    .get(&weapon_id)
                     .unwrap_or(&BulletCount::Count(0));
                 match count_type {
                     BulletCount::Unlimited => (),
@@ -140,25 +165,23 @@ pub fn update_battle_ui<'a>(
                         if count == 0 {
                             return;
                         } else {
-                            player.inventory.insert(bullet_type, count_type.decrement());
+                            player.inventory.insert(weapon_id, count_type.decrement());
                         }
                     }
                 }
-                let angle = &tank.shooting_direction.get();
-                let x_unit_vec = (angle).cos() * -1.0;
+                let velocity = launch_velocity(tank.shooting_direction.get(), player.fire_velocity);
+                let origin = transform.translation + Vec3::Y * MUZZLE_OFFSET;
                 let info = BulletInfo {
-                    velocity: &Vec2 {
-                        x: x_unit_vec * player.fire_velocity,
-                        y: (angle).sin() * player.fire_velocity,
-                    },
-                    origin: &transform.translation,
+                    velocity: &velocity,
+                    origin: &origin,
                     owner: player.player_number,
                 };
-                (player.selected_bullet.1)(
+                spawn_bullet(
                     &mut commands,
                     &mut meshes,
                     &mut materials,
                     &asset_server,
+                    weapon,
                     &info,
                 );
             }
@@ -168,9 +191,8 @@ pub fn update_battle_ui<'a>(
             BattleMessage::SetAngle(angle) => {
                 tank.shooting_direction.set(*angle);
             }
-            BattleMessage::SelectBullet(bullet) => {
-                let bullet_fn = bullet.get_bullet_from_type();
-                player.selected_bullet = (*bullet, bullet_fn);
+            BattleMessage::SelectBullet(weapon) => {
+                player.selected_weapon = weapon.clone();
             }
         }
     }
@@ -182,9 +204,9 @@ fn wrap(msg: BattleMessage) -> UiMessage {
     UiMessage::BattleMessage(msg)
 }
 
-fn bullet_picker(player: &Player) -> impl Into<IcedElement> {
-    let options: Vec<BulletType> = player.inventory.keys().copied().collect();
-    let selected = Some(player.selected_bullet.0);
+fn bullet_picker(player: &Player, weapons: &WeaponRegistry) -> impl Into<IcedElement> {
+    let options = weapons.owned(&player.inventory);
+    let selected = Some(player.selected_weapon.clone());
     column![bevy_iced::iced::widget::pick_list(
         options,
         selected,
@@ -198,7 +220,7 @@ fn fuel(player: &Player) -> impl Into<IcedElement> {
         button(text("left"))
             .on_press(wrap(BattleMessage::MoveLeft))
             .padding(5),
-        text(format!("Fuel: {}", player.fuel)),
+        text(format!("Fuel: {:.0}", player.fuel)),
         button(text("right"))
             .on_press(wrap(BattleMessage::MoveRight))
             .padding(5)
@@ -235,12 +257,14 @@ fn firing(player: &Player, tank: &Tank) -> impl Into<IcedElement> {
     .spacing(10)
 }
 
-fn info_box(wind: f32, player: &Player) -> impl Into<IcedElement> {
+fn info_box(state: &GameState, player: &Player) -> impl Into<IcedElement> {
     // TODO display properly
     column![
-        text(format!("Wind: {:.2}", wind)),
+        text(format!("Wind: {:+.0}", state.wind)),
+        text(format!("Map seed: {}", state.terrain_seed)),
         text(format!("Player: {}", player.player_number)),
         text(format!("Health: {}", player.health)),
         text(format!("Money: {}", player.money)),
+        text(format!("Parachutes: {}", player.parachutes)),
     ]
 }
@@ -1,3 +1,10 @@
+use crate::{
+    bullets::BulletCount,
+    tank::{Tank, MAX_PARACHUTES, PARACHUTE_COST},
+    utils::{EndTurnEvent, GameState, Player},
+    weapons::{WeaponDefinition, WeaponId, WeaponRegistry},
+    UiMessage,
+};
 use bevy::{
     prelude::{Entity, EventWriter, Query, Res, ResMut, Transform},
     sprite::Sprite,
@@ -9,20 +16,13 @@ use bevy_iced::{
     },
     IcedContext, Renderer,
 };
-use enum_iterator::all;
-
-use crate::{
-    bullets::{BulletCount, BulletType},
-    tank::Tank,
-    utils::{EndTurnEvent, GameState, Player},
-    UiMessage,
-};
 
 use super::utils::black_background::get_custom_container_style;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub enum ShopMessage {
-    BuyItem(BulletType),
+    BuyItem(WeaponId),
+    BuyParachute,
     EndTurn,
 }
 
@@ -31,6 +31,7 @@ pub fn update_shop_ui<'a>(
     state: ResMut<GameState>,
     mut query: Query<(Entity, &mut Player, &mut Tank, &mut Transform, &mut Sprite)>,
     mut end_turn_writer: EventWriter<EndTurnEvent>,
+    weapons: Res<WeaponRegistry>,
 ) {
     let msgs: Vec<&ShopMessage> = messages
         .filter_map(|val| match val {
@@ -47,14 +48,22 @@ pub fn update_shop_ui<'a>(
     if let Some(mut player) = current_player_opt {
         for msg in msgs {
             match msg {
-                ShopMessage::BuyItem(bullet_type) => {
-                    let cost = bullet_type.get_cost();
+                ShopMessage::BuyItem(weapon_id) => {
+                    let cost = if let Some(weapon) = weapons.get(weapon_id) {
+                        weapon.cost
+                    } else {
+                        continue;
+                    };
                     player.money -= cost;
                     let old = *player
                         .inventory
-                        .get(bullet_type)
+                        .get(weapon_id)
                         .unwrap_or(&BulletCount::Count(0));
-                    player.inventory.insert(*bullet_type, old.increment());
+                    player.inventory.insert(weapon_id.clone(), old.increment());
+                }
+                ShopMessage::BuyParachute => {
+                    player.money -= PARACHUTE_COST;
+                    player.parachutes += 1;
                 }
                 ShopMessage::EndTurn => {
                     end_turn_writer.send(EndTurnEvent {});
@@ -67,6 +76,7 @@ pub fn update_shop_ui<'a>(
 pub fn view_shop_ui(
     state: Res<GameState>,
     player_query: Query<(&Player, &Tank)>,
+    weapons: Res<WeaponRegistry>,
     mut ctx: IcedContext<UiMessage>,
 ) {
     let wrap = UiMessage::ShopMessage;
@@ -77,31 +87,51 @@ pub fn view_shop_ui(
         }
     }
     if let Some(player) = current_player_opt {
-        let item_container = |elem: &BulletType| -> Option<Container<UiMessage, Theme, Renderer>> {
-            let current_count = player.inventory.get(elem).unwrap_or(&BulletCount::Count(0));
-            let cost = elem.get_cost();
-            let max_count = elem.get_max_count();
-            if let BulletCount::Count(count) = current_count {
-                Some(container(column![
-                    text(format!("Cost: {}, You currently have: {}", cost, count)),
-                    button("buy").on_press_maybe(if cost <= player.money && *count < max_count {
-                        Some(wrap(ShopMessage::BuyItem(*elem)))
-                    } else {
-                        None
-                    }),
-                ]))
-            } else {
-                None
-            }
-        };
+        let item_container =
+            |weapon: &WeaponDefinition| -> Option<Container<UiMessage, Theme, Renderer>> {
+                let id = weapon.id();
+                let current_count = player.inventory.get(&id).unwrap_or(&BulletCount::Count(0));
+                let cost = weapon.cost;
+                if let (BulletCount::Count(count), Some(max_count)) =
+                    (current_count, weapon.max_count)
+                {
+                    Some(container(column![
+                        text(format!(
+                            "{} - Cost: {}, You currently have: {}",
+                            weapon.name, cost, count
+                        )),
+                        button("buy").on_press_maybe(
+                            if cost <= player.money && *count < max_count {
+                                Some(wrap(ShopMessage::BuyItem(id)))
+                            } else {
+                                None
+                            }
+                        ),
+                    ]))
+                } else {
+                    None
+                }
+            };
         let battle_button = button(text("confirm")).on_press(wrap(ShopMessage::EndTurn));
-        let bullets = all::<BulletType>().collect::<Vec<_>>();
         let bullet_items: Vec<Container<UiMessage, Theme, Renderer>> =
-            bullets.iter().filter_map(item_container).collect();
+            weapons.iter().filter_map(item_container).collect();
         let mut bullet_container = column![];
         for bullet in bullet_items {
             bullet_container = bullet_container.push(bullet);
         }
+        bullet_container = bullet_container.push(container(column![
+            text(format!(
+                "Parachute - Cost: {}, You currently have: {}",
+                PARACHUTE_COST, player.parachutes
+            )),
+            button("buy").on_press_maybe(
+                if PARACHUTE_COST <= player.money && player.parachutes < MAX_PARACHUTES {
+                    Some(wrap(ShopMessage::BuyParachute))
+                } else {
+                    None
+                }
+            ),
+        ]));
         ctx.display(
             container(column![
                 row![battle_button].padding(5),
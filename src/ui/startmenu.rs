@@ -1,14 +1,24 @@
-use bevy::prelude::{EventWriter, Res, ResMut};
+use bevy::{
+    asset::{AssetServer, Assets},
+    prelude::{EventWriter, Res, ResMut},
+};
 use bevy_iced::{
     iced::{
         alignment::{Horizontal, Vertical},
-        widget::{button, column, container, row, text, text_input},
+        widget::{button, column, container, pick_list, row, text, text_input},
     },
     IcedContext,
 };
+use enum_iterator::all;
 
 use crate::{
+    terrain::{
+        generator::TerrainPreset,
+        map::{MapAsset, MapLibrary, MapNotLoaded, MapSource},
+    },
+    trajectory::TrajectoryPreview,
     utils::{GameMode, GameState, ResetEvent},
+    world::{EdgeMode, WorldBounds},
     UiMessage,
 };
 
@@ -17,12 +27,17 @@ use super::utils::black_background::get_custom_container_style;
 #[derive(Clone)]
 pub enum StartMenuMessage {
     ChoosePlayerCount(String),
+    ChooseTerrainPreset(TerrainPreset),
+    ChooseMap(MapSource),
+    ChooseEdgeMode(EdgeMode),
+    ChooseTrajectoryPreview(TrajectoryPreview),
     Start,
 }
 
 pub fn update_startmenu_ui<'a>(
     messages: impl Iterator<Item = &'a UiMessage>,
     mut state: ResMut<GameState>,
+    mut bounds: ResMut<WorldBounds>,
     mut reset_writer: EventWriter<ResetEvent>,
 ) {
     let msgs: Vec<&StartMenuMessage> = messages
@@ -47,6 +62,18 @@ pub fn update_startmenu_ui<'a>(
                     }
                 }
             }
+            StartMenuMessage::ChooseTerrainPreset(preset) => {
+                state.terrain_preset = *preset;
+            }
+            StartMenuMessage::ChooseMap(source) => {
+                state.map_source = source.clone();
+            }
+            StartMenuMessage::ChooseEdgeMode(mode) => {
+                bounds.edge_mode = *mode;
+            }
+            StartMenuMessage::ChooseTrajectoryPreview(preview) => {
+                state.trajectory_preview = *preview;
+            }
             StartMenuMessage::Start => {
                 state.mode = GameMode::Battle;
                 reset_writer.send(ResetEvent {});
@@ -55,22 +82,65 @@ pub fn update_startmenu_ui<'a>(
     }
 }
 
-pub fn view_startmenu_ui(state: Res<GameState>, mut ctx: IcedContext<UiMessage>) {
+pub fn view_startmenu_ui(
+    state: Res<GameState>,
+    maps: Res<MapLibrary>,
+    map_assets: Res<Assets<MapAsset>>,
+    asset_server: Res<AssetServer>,
+    bounds: Res<WorldBounds>,
+    mut ctx: IcedContext<UiMessage>,
+) {
     let wrap = UiMessage::StartMenuMessage;
     let title = text("Tankrs");
-    let start_button = button("Start").on_press_maybe(if state.player_count_parse_error {
-        None
-    } else {
-        Some(UiMessage::SetSceneMessage(GameMode::Battle))
-    });
+    // a map file is only played once it is loaded, the round waits until then
+    let map_status = maps.selected(&state.map_source, &map_assets, &asset_server);
+    let map_loaded = map_status.is_ok();
+    let start_button =
+        button("Start").on_press_maybe(if state.player_count_parse_error || !map_loaded {
+            None
+        } else {
+            Some(wrap(StartMenuMessage::Start))
+        });
     let input = text_input("Player Count", &state.player_count_input)
         .on_input(|count| wrap(StartMenuMessage::ChoosePlayerCount(count)));
-    let content_container =
-        container(column![title, row![input, start_button].spacing(5)].spacing(10))
-            .width(300)
-            .height(600)
-            .align_x(Horizontal::Center)
-            .align_y(Vertical::Center);
+    let presets = all::<TerrainPreset>().collect::<Vec<_>>();
+    let preset_picker = pick_list(presets, Some(state.terrain_preset), |preset| {
+        wrap(StartMenuMessage::ChooseTerrainPreset(preset))
+    });
+    let map_sources = std::iter::once(MapSource::Generated)
+        .chain(maps.names().cloned().map(MapSource::File))
+        .collect::<Vec<_>>();
+    let map_picker = pick_list(map_sources, Some(state.map_source.clone()), |source| {
+        wrap(StartMenuMessage::ChooseMap(source))
+    });
+    let edge_modes = all::<EdgeMode>().collect::<Vec<_>>();
+    let edge_mode_picker = pick_list(edge_modes, Some(bounds.edge_mode), |mode| {
+        wrap(StartMenuMessage::ChooseEdgeMode(mode))
+    });
+    let previews = all::<TrajectoryPreview>().collect::<Vec<_>>();
+    let preview_picker = pick_list(previews, Some(state.trajectory_preview), |preview| {
+        wrap(StartMenuMessage::ChooseTrajectoryPreview(preview))
+    });
+    let content_container = container(
+        column![
+            title,
+            row![input, start_button].spacing(5),
+            row![text("Map"), map_picker].spacing(5),
+            text(match map_status {
+                Ok(_) => "",
+                Err(MapNotLoaded::Loading) => "Loading map...",
+                Err(MapNotLoaded::Failed) => "The map failed to load, pick another one",
+            }),
+            row![text("Terrain"), preset_picker].spacing(5),
+            row![text("World edges"), edge_mode_picker].spacing(5),
+            row![text("Trajectory preview"), preview_picker].spacing(5)
+        ]
+        .spacing(10),
+    )
+    .width(300)
+    .height(600)
+    .align_x(Horizontal::Center)
+    .align_y(Vertical::Center);
     ctx.display(
         container(content_container)
             .padding(10)
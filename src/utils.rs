@@ -1,16 +1,24 @@
 use bevy::{
-    asset::{AssetServer, Assets},
+    math::Vec2,
     prelude::{
-        Commands, Component, Entity, Event, Mesh, Mut, Query, Res, ResMut, Resource, Transform,
+        Commands, Component, DespawnRecursiveExt, Entity, Event, EventWriter, Mut, Query, Resource,
+        Transform,
     },
-    sprite::{ColorMaterial, Sprite},
+    sprite::Sprite,
     utils::HashMap,
 };
 
 use crate::{
-    bullets::{BulletCount, BulletInfo, BulletType, NORMAL_BULLET},
+    bullets::{BulletCount, Falloff},
     inputs::KeyMap,
-    tank::Tank,
+    tank::{Tank, DRIVE_SPEED, FUEL_PER_UNIT, UPHILL_FUEL_FACTOR},
+    terrain::{
+        generator::TerrainPreset,
+        map::{MapRules, MapSource},
+        Terrain,
+    },
+    trajectory::{TrajectoryPreview, DEFAULT_SHORT_PREVIEW_STEPS},
+    weapons::{WeaponId, WeaponRegistry},
 };
 
 #[derive(Event)]
@@ -22,6 +30,55 @@ pub struct PlayerKillEvent {
 #[derive(Event)]
 pub struct FireEvent {}
 
+/// Explosion at `position` damaging every tank within `radius` and carving the terrain.
+#[derive(Event)]
+pub struct ExplosionEvent {
+    pub position: Vec2,
+    pub radius: f32,
+    pub damage: u32,
+    pub falloff: Falloff,
+    pub owner: u32,
+}
+
+/// Sent once for every projectile entering the world, including split sub-munitions.
+#[derive(Event)]
+pub struct ProjectileSpawned {
+    pub entity: Entity,
+    pub weapon: WeaponId,
+    pub owner: u32,
+}
+
+/// What a projectile hit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImpactTarget {
+    Terrain,
+    Tank(Entity),
+    /// Triggered in mid-air, e.g. by a fuse or a cluster split.
+    Air,
+}
+
+/// Sent when a projectile hits something. The projectile is still alive when handlers read this,
+/// `detonate_projectiles` turns it into an explosion afterwards.
+#[derive(Event)]
+pub struct ProjectileImpact {
+    pub entity: Entity,
+    pub weapon: WeaponId,
+    pub owner: u32,
+    pub position: Vec2,
+    /// Unit vector pointing away from the hit surface.
+    pub normal: Vec2,
+    pub target: ImpactTarget,
+}
+
+/// Sent whenever a projectile is removed, be it by an impact, liquid or the world edge.
+#[derive(Event)]
+pub struct ProjectileDespawned {
+    pub entity: Entity,
+    pub weapon: WeaponId,
+    pub owner: u32,
+    pub position: Vec2,
+}
+
 #[derive(Event)]
 pub struct EndTurnEvent {}
 
@@ -50,6 +107,12 @@ pub struct GameState {
     pub player_count_input: String,
     pub player_count_parse_error: bool,
     pub wind: f32,
+    pub terrain_seed: u64,
+    pub terrain_preset: TerrainPreset,
+    pub map_source: MapSource,
+    pub trajectory_preview: TrajectoryPreview,
+    /// Number of fixed steps shown by `TrajectoryPreview::Short`.
+    pub short_preview_steps: usize,
 }
 
 impl GameState {
@@ -71,19 +134,18 @@ impl Default for GameState {
             player_count_input: "2".into(),
             player_count: 2,
             player_count_parse_error: false,
-            wind: random_wind(),
+            wind: MapRules::default().random_wind(),
+            terrain_seed: rand::random(),
+            terrain_preset: TerrainPreset::RollingHills,
+            map_source: MapSource::Generated,
+            trajectory_preview: TrajectoryPreview::Off,
+            short_preview_steps: DEFAULT_SHORT_PREVIEW_STEPS,
         }
     }
 }
 
-pub type BulletFn = fn(
-    &mut Commands,
-    &mut ResMut<Assets<Mesh>>,
-    &mut ResMut<Assets<ColorMaterial>>,
-    &Res<AssetServer>,
-    &BulletInfo,
-);
-pub type BulletTypeAndFn = (BulletType, BulletFn);
+/// Money, inventory and parachutes kept between rounds.
+pub type PreviousPlayerState = (u32, HashMap<WeaponId, BulletCount>, u32);
 
 pub type PlayerProps<'a> = Option<(
     Entity,
@@ -96,69 +158,71 @@ pub type PlayerProps<'a> = Option<(
 #[derive(Component, Clone)]
 pub struct Player {
     pub player_number: u32,
-    pub inventory: HashMap<BulletType, BulletCount>,
-    pub selected_bullet: BulletTypeAndFn,
+    pub inventory: HashMap<WeaponId, BulletCount>,
+    pub selected_weapon: WeaponId,
     pub health: i32,
-    pub fuel: u32,
+    pub fuel: f32,
     pub money: u32,
     pub key_map: KeyMap,
     pub fire_velocity: f32,
+    pub parachutes: u32,
 }
 
 impl Player {
-    pub fn selected_bullet(&self) -> BulletFn {
-        self.selected_bullet.1
-    }
-
-    /// Returns the x axis change according to fuel used
-    pub fn drive(&mut self, fuel_change: u32) -> f32 {
-        self.fuel = if self.fuel > fuel_change {
-            self.fuel - fuel_change
-        } else {
-            0
-        };
-        self.fuel as f32
+    /// Returns the x axis change for driving `tank` at `x` in `direction` (-1.0 or 1.0).
+    /// Uphill driving burns more fuel, too steep slopes, other tanks and the map edges block.
+    pub fn drive(
+        &mut self,
+        tank: &Tank,
+        x: f32,
+        direction: f32,
+        delta: f32,
+        terrain: &Terrain,
+        other_tanks: &[f32],
+    ) -> f32 {
+        let distance = DRIVE_SPEED * delta;
+        let target = x + direction * distance;
+        if tank.blocked(x, target, terrain, other_tanks) {
+            return 0.0;
+        }
+        let slope = terrain.slope_angle_at(target, tank.scale.x) * direction;
+        let fuel_used =
+            distance * FUEL_PER_UNIT * (1.0 + UPHILL_FUEL_FACTOR * slope.tan().max(0.0));
+        if self.fuel < fuel_used {
+            return 0.0;
+        }
+        self.fuel -= fuel_used;
+        target - x
     }
 
     pub fn from_previous_or_initial(
         index: u32,
-        props_opt: Option<&(u32, HashMap<BulletType, BulletCount>)>,
+        props_opt: Option<&PreviousPlayerState>,
+        weapons: &WeaponRegistry,
     ) -> Player {
-        let (inventory, money) = if let Some(props) = props_opt {
-            (props.1.clone(), props.0)
+        let (mut inventory, money, parachutes) = if let Some(props) = props_opt {
+            (props.1.clone(), props.0, props.2)
         } else {
-            (BulletType::init_bullets(), 0)
+            (HashMap::new(), 0, 0)
         };
+        // players created before the weapons finished loading have nothing to carry over
+        if inventory.is_empty() {
+            inventory = weapons.starting_inventory();
+        }
         Player {
             player_number: index,
             inventory,
             health: 100,
-            fuel: 1000,
+            fuel: 1000.0,
             money,
             key_map: KeyMap::default_keymap(),
-            selected_bullet: (BulletType::RegularBullet, NORMAL_BULLET),
+            selected_weapon: weapons.default_weapon(),
             fire_velocity: 1.0,
+            parachutes,
         }
     }
 }
 
-pub fn polynomial(x: i32, rand: f32) -> f32 {
-    let x = x as f32;
-    //(f32::consts::E - x) * (x * f32::consts::E) *
-    //(rand * power(x, 4)) + (rand * power(x, 3)) - (rand * power(x, 2)) - (rand * x)
-    //power(x, 5) - power(x, 4) - (5.0 * power(x, 3)) + (3.0 * power(x, 2)) + (4.0 * x) - 3.0
-    //((rand * x) * 0.00005).cos() * 1000.0 * rand
-    (x * rand * 0.005).cos() * 100. * rand + 100.0
-}
-
-pub fn power(_num: f32, pow: i32) -> f32 {
-    if pow > 0 {
-        power(_num, pow - 1)
-    } else {
-        1.0
-    }
-}
-
 pub fn get_current_player_props<'a>(
     active_player_index: u32,
     query: &'a mut Query<(Entity, &mut Player, &mut Tank, &mut Transform, &mut Sprite)>,
@@ -183,6 +247,24 @@ pub fn get_current_player_props<'a>(
     }
 }
 
-pub fn random_wind() -> f32 {
-    rand::random::<f32>().clamp(-0.3, 0.3)
+/// Deals `damage` to the player of the tank `entity`. Only the hit that takes the player from
+/// alive to dead credits `killer` with a `PlayerKillEvent` and removes the tank, so players
+/// hurt again before their tank is gone aren't killed twice.
+pub fn damage_tank(
+    commands: &mut Commands,
+    kill_writer: &mut EventWriter<PlayerKillEvent>,
+    entity: Entity,
+    player: &mut Player,
+    damage: i32,
+    killer: u32,
+) {
+    let alive = player.health >= 0;
+    player.health -= damage;
+    if alive && player.health < 0 {
+        kill_writer.send(PlayerKillEvent {
+            killer,
+            killed: player.player_number,
+        });
+        commands.entity(entity).despawn_recursive();
+    }
 }
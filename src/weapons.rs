@@ -0,0 +1,292 @@
+use std::fmt::Display;
+
+use bevy::{
+    asset::{
+        io::Reader, Asset, AssetEvent, AssetLoader, Assets, AsyncReadExt, Handle, LoadContext,
+        LoadedFolder,
+    },
+    prelude::{Component, DetectChanges, EventReader, Query, Res, ResMut, Resource},
+    reflect::TypePath,
+    utils::HashMap,
+};
+use serde::Deserialize;
+
+use crate::{
+    bullets::{BulletCount, Falloff},
+    utils::Player,
+};
+
+/// Weapon described by a `*.weapon.ron` file in `assets/weapons`.
+#[derive(Asset, TypePath, Deserialize, Clone)]
+pub struct WeaponDefinition {
+    /// Unique name, shown in the shop and battle UI.
+    pub name: String,
+    pub cost: u32,
+    /// Most a player can own, weapons without a limit are given to everyone and not sold.
+    #[serde(default)]
+    pub max_count: Option<u32>,
+    /// Count every player starts the first round with.
+    #[serde(default)]
+    pub starting_count: u32,
+    pub damage: u32,
+    /// Radius of the explosion on impact.
+    pub radius: f32,
+    #[serde(default)]
+    pub falloff: Falloff,
+    /// Heavier projectiles are pushed less by wind and air drag.
+    #[serde(default = "default_mass")]
+    pub mass: f32,
+    /// How strongly the air acts on the projectile.
+    #[serde(default = "default_drag")]
+    pub drag: f32,
+    pub appearance: WeaponAppearance,
+    #[serde(default)]
+    pub behaviors: Vec<WeaponBehavior>,
+}
+
+fn default_mass() -> f32 {
+    1.0
+}
+
+fn default_drag() -> f32 {
+    1.0
+}
+
+impl WeaponDefinition {
+    pub fn id(&self) -> WeaponId {
+        WeaponId(self.name.clone())
+    }
+
+    /// Drag to mass ratio, scales how much wind and air drag affect the projectile.
+    pub fn exposure(&self) -> f32 {
+        self.drag / self.mass
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub enum WeaponAppearance {
+    /// Plain circle with an sRGB color.
+    Mesh { radius: f32, color: (f32, f32, f32) },
+    /// Image relative to the assets folder, `size` world units wide or at its own size.
+    Sprite {
+        path: String,
+        #[serde(default)]
+        size: Option<f32>,
+    },
+}
+
+/// Special effects of a projectile on top of flying and exploding.
+#[derive(Deserialize, Clone)]
+pub enum WeaponBehavior {
+    /// Explodes in mid-air `seconds` after being fired.
+    Fuse { seconds: f32 },
+    /// Splits into `count` projectiles of `child` fanned out over `spread` radians around the
+    /// current direction.
+    Cluster {
+        count: u32,
+        spread: f32,
+        trigger: SplitTrigger,
+        child: Box<WeaponDefinition>,
+    },
+    /// Sets `width` world units of ground around the impact on fire for `turns` turns, damaging
+    /// tanks starting their turn in it.
+    Ignite { width: f32, turns: u32, damage: i32 },
+    /// Plays the frames of the GIF at `path`, `size` world units wide, where the projectile hits.
+    ExplosionAnimation { path: String, size: f32 },
+    /// Shakes the camera by up to `strength` world units, fading out over `seconds`.
+    ScreenShake { strength: f32, seconds: f32 },
+    /// Rolls along the terrain after touching the ground, slowed by `friction` in world units per
+    /// second squared, and explodes once it stops or touches a tank.
+    Roll { friction: f32 },
+    /// Burrows up to `distance` world units through the ground instead of exploding on contact,
+    /// carving a tunnel of `radius`. Explodes at the end of the tunnel if `detonate` is set.
+    Dig {
+        distance: f32,
+        radius: f32,
+        detonate: bool,
+    },
+    /// Pushes tanks in the blast radius up to `distance` world units away from the impact.
+    Knockback { distance: f32 },
+}
+
+/// When a cluster projectile splits.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum SplitTrigger {
+    /// At the highest point of the flight.
+    Apex,
+    /// The given number of seconds after being fired.
+    Timer(f32),
+}
+
+/// Name of a weapon in the `WeaponRegistry`, also attached to every fired projectile.
+#[derive(Component, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct WeaponId(pub String);
+
+impl Display for WeaponId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[derive(Debug)]
+pub enum WeaponLoadError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+}
+
+impl Display for WeaponLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WeaponLoadError::Io(error) => write!(f, "Could not read weapon: {}", error),
+            WeaponLoadError::Ron(error) => write!(f, "Could not parse weapon: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for WeaponLoadError {}
+
+impl From<std::io::Error> for WeaponLoadError {
+    fn from(value: std::io::Error) -> Self {
+        WeaponLoadError::Io(value)
+    }
+}
+
+impl From<ron::error::SpannedError> for WeaponLoadError {
+    fn from(value: ron::error::SpannedError) -> Self {
+        WeaponLoadError::Ron(value)
+    }
+}
+
+#[derive(Default)]
+pub struct WeaponLoader;
+
+impl AssetLoader for WeaponLoader {
+    type Asset = WeaponDefinition;
+    type Settings = ();
+    type Error = WeaponLoadError;
+
+    async fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader<'_>,
+        _: &'a (),
+        _: &'a mut LoadContext<'_>,
+    ) -> Result<WeaponDefinition, WeaponLoadError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes::<WeaponDefinition>(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["weapon.ron"]
+    }
+}
+
+/// All weapons found in `assets/weapons`, ordered by cost.
+#[derive(Resource)]
+pub struct WeaponRegistry {
+    folder: Handle<LoadedFolder>,
+    weapons: Vec<WeaponDefinition>,
+}
+
+impl WeaponRegistry {
+    pub fn new(folder: Handle<LoadedFolder>) -> Self {
+        WeaponRegistry {
+            folder,
+            weapons: Vec::new(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WeaponDefinition> {
+        self.weapons.iter()
+    }
+
+    pub fn get(&self, id: &WeaponId) -> Option<&WeaponDefinition> {
+        self.weapons.iter().find(|weapon| weapon.name == id.0)
+    }
+
+    /// Returns the first weapon without a limit, which every player always owns.
+    pub fn default_weapon(&self) -> WeaponId {
+        self.weapons
+            .iter()
+            .find(|weapon| weapon.max_count.is_none())
+            .map(WeaponDefinition::id)
+            .unwrap_or_default()
+    }
+
+    pub fn starting_inventory(&self) -> HashMap<WeaponId, BulletCount> {
+        self.weapons
+            .iter()
+            .filter_map(|weapon| match weapon.max_count {
+                None => Some((weapon.id(), BulletCount::Unlimited)),
+                Some(_) if weapon.starting_count > 0 => {
+                    Some((weapon.id(), BulletCount::Count(weapon.starting_count)))
+                }
+                Some(_) => None,
+            })
+            .collect()
+    }
+
+    /// Returns the weapons of `inventory` in registry order.
+    pub fn owned(&self, inventory: &HashMap<WeaponId, BulletCount>) -> Vec<WeaponId> {
+        self.weapons
+            .iter()
+            .map(WeaponDefinition::id)
+            .filter(|id| inventory.contains_key(id))
+            .collect()
+    }
+
+    /// Returns the owned weapon after `current`, wrapping around to the first one.
+    pub fn next_owned(
+        &self,
+        current: &WeaponId,
+        inventory: &HashMap<WeaponId, BulletCount>,
+    ) -> Option<WeaponId> {
+        let owned = self.owned(inventory);
+        let next = owned
+            .iter()
+            .position(|id| id == current)
+            .map_or(0, |index| (index + 1) % owned.len());
+        owned.get(next).cloned()
+    }
+}
+
+/// Hands the starting inventory and default weapon to players created before the weapons
+/// finished loading.
+pub fn equip_players(registry: Res<WeaponRegistry>, mut players: Query<&mut Player>) {
+    if !registry.is_changed() {
+        return;
+    }
+    for mut player in &mut players {
+        if player.inventory.is_empty() {
+            player.inventory = registry.starting_inventory();
+        }
+        if registry.get(&player.selected_weapon).is_none() {
+            player.selected_weapon = registry.default_weapon();
+        }
+    }
+}
+
+pub fn collect_weapons(
+    mut registry: ResMut<WeaponRegistry>,
+    mut events: EventReader<AssetEvent<LoadedFolder>>,
+    folders: Res<Assets<LoadedFolder>>,
+    definitions: Res<Assets<WeaponDefinition>>,
+) {
+    for event in events.read() {
+        if !event.is_loaded_with_dependencies(&registry.folder) {
+            continue;
+        }
+        if let Some(folder) = folders.get(&registry.folder) {
+            let mut weapons: Vec<WeaponDefinition> = folder
+                .handles
+                .iter()
+                .filter_map(|handle| {
+                    let handle = handle.clone().try_typed::<WeaponDefinition>().ok()?;
+                    definitions.get(&handle).cloned()
+                })
+                .collect();
+            weapons.sort_by(|a, b| a.cost.cmp(&b.cost).then_with(|| a.name.cmp(&b.name)));
+            registry.weapons = weapons;
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt::Display;
+
+use bevy::{
+    math::Vec2,
+    prelude::{
+        Camera2d, Commands, DespawnRecursiveExt, DetectChanges, Entity, EventWriter,
+        OrthographicProjection, Query, Res, Resource, Transform, With,
+    },
+    render::camera::ScalingMode,
+};
+use enum_iterator::Sequence;
+
+use crate::{bullets::Bullet, utils::ProjectileDespawned, weapons::WeaponId};
+
+/// What happens to projectiles leaving the sides of the world.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Sequence)]
+pub enum EdgeMode {
+    /// Projectiles bounce off solid walls.
+    Walls,
+    /// Projectiles reappear on the other side.
+    WrapAround,
+    /// Projectiles are removed.
+    Open,
+}
+
+impl Display for EdgeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mode_str = match self {
+            EdgeMode::Walls => "Walls",
+            EdgeMode::WrapAround => "Wrap Around",
+            EdgeMode::Open => "Open",
+        };
+        f.write_str(mode_str)
+    }
+}
+
+/// Extent of the playable world in world units, independent of the window size.
+/// The terrain starts at `bottom`, the camera always shows the whole area.
+#[derive(Resource)]
+pub struct WorldBounds {
+    pub left: f32,
+    pub right: f32,
+    pub bottom: f32,
+    pub top: f32,
+    pub edge_mode: EdgeMode,
+}
+
+impl Default for WorldBounds {
+    fn default() -> Self {
+        WorldBounds {
+            left: -1920.0,
+            right: 1920.0,
+            bottom: -1080.0,
+            top: 1080.0,
+            edge_mode: EdgeMode::Open,
+        }
+    }
+}
+
+impl WorldBounds {
+    pub fn width(&self) -> f32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> f32 {
+        self.top - self.bottom
+    }
+
+    pub fn center(&self) -> Vec2 {
+        Vec2::new(
+            (self.left + self.right) / 2.0,
+            (self.bottom + self.top) / 2.0,
+        )
+    }
+
+    /// X coordinate of the `index`th of `count` evenly spaced positions across the world.
+    pub fn spread_x(&self, index: u32, count: u32) -> f32 {
+        self.left + self.width() * (index + 1) as f32 / (count + 1) as f32
+    }
+}
+
+/// Scales and centers the camera so the whole world is visible at any window size.
+pub fn fit_camera(
+    bounds: Res<WorldBounds>,
+    mut cameras: Query<(&mut OrthographicProjection, &mut Transform), With<Camera2d>>,
+) {
+    if !bounds.is_changed() {
+        return;
+    }
+    for (mut projection, mut transform) in &mut cameras {
+        projection.scaling_mode = ScalingMode::AutoMin {
+            min_width: bounds.width(),
+            min_height: bounds.height(),
+        };
+        let center = bounds.center();
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+    }
+}
+
+pub fn apply_world_edges(
+    mut commands: Commands,
+    bounds: Res<WorldBounds>,
+    mut bullets: Query<(Entity, &mut Bullet, &WeaponId, &mut Transform)>,
+    mut despawn_writer: EventWriter<ProjectileDespawned>,
+) {
+    for (entity, mut bullet, weapon, mut transform) in &mut bullets {
+        let x = transform.translation.x;
+        if x >= bounds.left && x <= bounds.right {
+            continue;
+        }
+        match bounds.edge_mode {
+            EdgeMode::Walls => {
+                let wall = if x < bounds.left {
+                    bounds.left
+                } else {
+                    bounds.right
+                };
+                transform.translation.x = 2.0 * wall - x;
+                bullet.previous_position.x = 2.0 * wall - bullet.previous_position.x;
+                bullet.velocity_shot.x = -bullet.velocity_shot.x;
+            }
+            EdgeMode::WrapAround => {
+                let shift = if x < bounds.left {
+                    bounds.width()
+                } else {
+                    -bounds.width()
+                };
+                transform.translation.x += shift;
+                bullet.previous_position.x += shift;
+            }
+            EdgeMode::Open => {
+                despawn_writer.send(ProjectileDespawned {
+                    entity,
+                    weapon: weapon.clone(),
+                    owner: bullet.owner,
+                    position: transform.translation.truncate(),
+                });
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}